@@ -0,0 +1,52 @@
+use satori::command::{ArgKind, Command, Commands};
+use satori::{Satori, SATORI};
+use std::net::{IpAddr, Ipv4Addr};
+use tracing_subscriber::filter::LevelFilter;
+
+#[tokio::main]
+async fn main() {
+    let filter = tracing_subscriber::filter::Targets::new()
+        .with_default(LevelFilter::INFO)
+        .with_targets([(SATORI, LevelFilter::TRACE)]);
+    use tracing_subscriber::{
+        prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt, Layer,
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(filter))
+        .init();
+    let commands = Commands::new()
+        .prefix("/")
+        .command(
+            Command::new("echo")
+                .alias("say")
+                .description("repeat the message")
+                .arg("text", ArgKind::Rest)
+                .handler(|ctx| async move {
+                    let text = ctx.args.str("text").unwrap_or_default();
+                    ctx.reply(text).await.ok();
+                }),
+        )
+        .command(
+            Command::new("add")
+                .description("add two numbers")
+                .arg("a", ArgKind::Int)
+                .arg("b", ArgKind::Int)
+                .handler(|ctx| async move {
+                    let sum = ctx.args.int("a").unwrap_or(0) + ctx.args.int("b").unwrap_or(0);
+                    ctx.reply(&sum.to_string()).await.ok();
+                }),
+        );
+    let app = Satori::new_app(commands);
+    app.start_and_wait(
+        vec![satori::NetSDKConfig {
+            host: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            port: 5140,
+            authorize: None,
+            reconnect: Default::default(),
+            api_timeout: std::time::Duration::from_secs(30),
+            mode: Default::default(),
+        }],
+        (),
+    )
+    .await;
+}
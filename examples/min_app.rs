@@ -1,5 +1,8 @@
-use satori::{AppT, ChannelType, Event, Satori, SdkT, SATORI};
-use serde_json::{json, Value};
+use satori::element;
+use satori::{
+    AppT, ChannelType, Event, EventKind, MessageEvent, Satori, SdkT, Session, Suppress,
+    WithMiddleware as _, SATORI,
+};
 use std::{
     net::{IpAddr, Ipv4Addr},
     sync::Arc,
@@ -24,57 +27,37 @@ impl AppT for Echo {
     {
         vec![]
     }
-    async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, mut event: Event)
+    #[allow(clippy::single_match)]
+    async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
     where
         S: SdkT + Send + Sync + 'static,
         A: AppT + Send + Sync + 'static,
     {
         info!("start_handle_evnet");
-        if let Some(user) = event.user {
-            if user.id == event.self_id {
-                info!("self event");
+        let session = Session::new(s, event);
+        if let EventKind::MessageCreated(MessageEvent {
+            message,
+            channel: ch,
+            ..
+        }) = session.event.kind()
+        {
+            if !message.content.starts_with("echo") {
                 return;
             }
-        }
-        if let Some(message) = event
-            .extra
-            .remove("message")
-            .and_then(|v| serde_json::from_value::<satori::Message>(v).ok())
-            .filter(|m| m.content.starts_with("echo"))
-        {
-            let bot = satori::BotId {
-                id: event.self_id,
-                platform: event.platform,
-            };
-            if let Some(ch) = event.channel {
-                match ch.ty {
-                    ChannelType::Text => {
-                        let r = s
-                            .call_api::<Value>(
-                                "message.create",
-                                &bot,
-                                json!({
-                                    "channel_id": ch.id,
-                                    "content": message.content
-                                }),
-                            )
-                            .await;
-                        println!("......r:{:?}", r);
-                    }
-                    // ChannelType::Direct => {
-                    //     let _ch = s
-                    //         .call_api::<Channel>(
-                    //             "user.channel.create",
-                    //             &bot,
-                    //             json!({
-                    //                 "user_id": ch.id,
-                    //             }),
-                    //         )
-                    //         .await
-                    //         .unwrap();
-                    // }
-                    _ => {}
+            match ch.ty {
+                ChannelType::Text => {
+                    let content = element::stringify(&message.elements());
+                    let r = session.quote_reply(&content).await;
+                    println!("......r:{:?}", r);
                 }
+                // ChannelType::Direct => {
+                //     let _ch = session
+                //         .bot()
+                //         .user_channel_create(&ch.id, None)
+                //         .await
+                //         .unwrap();
+                // }
+                _ => {}
             }
         }
     }
@@ -91,12 +74,15 @@ async fn main() {
     tracing_subscriber::registry()
         .with(tracing_subscriber::fmt::layer().with_filter(filter))
         .init();
-    let app = Satori::new_app(Echo {});
+    let app = Satori::new_app(Echo {}.layer(Suppress::new().self_messages()));
     app.start_and_wait(
         vec![satori::NetSDKConfig {
             host: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
             port: 5140,
             authorize: None,
+            reconnect: Default::default(),
+            api_timeout: std::time::Duration::from_secs(30),
+            mode: Default::default(),
         }],
         (),
     )
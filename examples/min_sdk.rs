@@ -29,7 +29,7 @@ impl SdkT for Echo {
         _bot: &BotId,
         _data: Value,
     ) -> Result<String, CallApiError> {
-        Err(CallApiError::ServerError(500))
+        Err(CallApiError::ServerError(500, "not implemented".to_owned()))
     }
     async fn get_logins(&self) -> Vec<Login> {
         vec![]
@@ -54,6 +54,9 @@ async fn main() {
             host: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
             port: 5141,
             authorize: None,
+            tokens: vec![],
+            webhooks: vec![],
+            upload_limit: 16 * 1024 * 1024,
         }],
     )
     .await;
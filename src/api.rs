@@ -0,0 +1,666 @@
+use async_trait::async_trait;
+use futures_util::Stream;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Object safe access to the apis of a `Satori`, for code that can not be
+/// generic over its sdk and app.
+#[async_trait]
+pub trait ApiCaller: Send + Sync {
+    async fn call_raw(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError>;
+    async fn call_internal_raw(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError>;
+    async fn upload_files(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError>;
+}
+
+#[async_trait]
+impl<S, A> ApiCaller for Satori<S, A>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    async fn call_raw(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError> {
+        self.s.call_api(api, bot, data).await
+    }
+    async fn call_internal_raw(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        self.s.call_internal(method, bot, data).await
+    }
+    async fn upload_files(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        self.s.upload(bot, files).await
+    }
+}
+
+/// Typed access to the standard Satori resource APIs on behalf of one bot.
+pub struct Bot<'a, C: ?Sized> {
+    s: &'a C,
+    id: &'a BotId,
+}
+
+impl<C: ?Sized> Clone for Bot<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: ?Sized> Copy for Bot<'_, C> {}
+
+impl<S, A> Satori<S, A>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    pub fn bot<'a>(&'a self, id: &'a BotId) -> Bot<'a, Self> {
+        Bot { s: self, id }
+    }
+}
+
+impl<'c> dyn ApiCaller + 'c {
+    pub fn bot<'a>(&'a self, id: &'a BotId) -> Bot<'a, Self> {
+        Bot { s: self, id }
+    }
+}
+
+macro_rules! owned {
+    ($($s:expr),*) => {
+        ($($s.to_owned()),*)
+    };
+}
+
+impl<'a, C> Bot<'a, C>
+where
+    C: ApiCaller + ?Sized,
+{
+    pub fn id(&self) -> &BotId {
+        self.id
+    }
+
+    pub async fn call<T, D>(&self, api: &str, data: D) -> Result<T, CallApiError>
+    where
+        T: DeserializeOwned,
+        D: Serialize,
+    {
+        let data = serde_json::to_value(data).map_err(CallApiError::SerializeFailed)?;
+        self.s
+            .call_raw(api, self.id, data)
+            .await
+            .and_then(parse_resp)
+    }
+
+    pub async fn internal<T, D>(&self, method: &str, data: D) -> Result<T, CallApiError>
+    where
+        T: DeserializeOwned,
+        D: Serialize,
+    {
+        let data = serde_json::to_value(data).map_err(CallApiError::SerializeFailed)?;
+        self.s
+            .call_internal_raw(method, self.id, data)
+            .await
+            .and_then(parse_resp)
+    }
+
+    // channel
+
+    pub async fn channel_get(&self, channel_id: &str) -> Result<Channel, CallApiError> {
+        let channel_id = owned!(channel_id);
+        self.call("channel.get", ChannelIdRequest { channel_id })
+            .await
+    }
+
+    pub async fn channel_list(
+        &self,
+        guild_id: &str,
+        next: Option<String>,
+    ) -> Result<List<Channel>, CallApiError> {
+        let guild_id = owned!(guild_id);
+        self.call("channel.list", GuildNextRequest { guild_id, next })
+            .await
+    }
+
+    pub async fn channel_create(
+        &self,
+        guild_id: &str,
+        data: ChannelData,
+    ) -> Result<Channel, CallApiError> {
+        let guild_id = owned!(guild_id);
+        self.call("channel.create", ChannelCreateRequest { guild_id, data })
+            .await
+    }
+
+    pub async fn channel_update(
+        &self,
+        channel_id: &str,
+        data: ChannelData,
+    ) -> Result<(), CallApiError> {
+        let channel_id = owned!(channel_id);
+        self.call("channel.update", ChannelUpdateRequest { channel_id, data })
+            .await
+    }
+
+    pub async fn channel_delete(&self, channel_id: &str) -> Result<(), CallApiError> {
+        let channel_id = owned!(channel_id);
+        self.call("channel.delete", ChannelIdRequest { channel_id })
+            .await
+    }
+
+    pub async fn channel_mute(&self, channel_id: &str, duration: u64) -> Result<(), CallApiError> {
+        let channel_id = owned!(channel_id);
+        self.call(
+            "channel.mute",
+            ChannelMuteRequest {
+                channel_id,
+                duration,
+            },
+        )
+        .await
+    }
+
+    pub async fn user_channel_create(
+        &self,
+        user_id: &str,
+        guild_id: Option<String>,
+    ) -> Result<Channel, CallApiError> {
+        let user_id = owned!(user_id);
+        self.call(
+            "user.channel.create",
+            UserChannelCreateRequest { user_id, guild_id },
+        )
+        .await
+    }
+
+    // guild
+
+    pub async fn guild_get(&self, guild_id: &str) -> Result<Guild, CallApiError> {
+        let guild_id = owned!(guild_id);
+        self.call("guild.get", GuildIdRequest { guild_id }).await
+    }
+
+    pub async fn guild_list(&self, next: Option<String>) -> Result<List<Guild>, CallApiError> {
+        self.call("guild.list", NextRequest { next }).await
+    }
+
+    pub async fn guild_approve(
+        &self,
+        message_id: &str,
+        approve: bool,
+        comment: Option<String>,
+    ) -> Result<(), CallApiError> {
+        let message_id = owned!(message_id);
+        self.call(
+            "guild.approve",
+            ApproveRequest {
+                message_id,
+                approve,
+                comment,
+            },
+        )
+        .await
+    }
+
+    // guild.member
+
+    pub async fn guild_member_get(
+        &self,
+        guild_id: &str,
+        user_id: &str,
+    ) -> Result<GuildMember, CallApiError> {
+        let (guild_id, user_id) = owned!(guild_id, user_id);
+        self.call("guild.member.get", GuildMemberRequest { guild_id, user_id })
+            .await
+    }
+
+    pub async fn guild_member_list(
+        &self,
+        guild_id: &str,
+        next: Option<String>,
+    ) -> Result<List<GuildMember>, CallApiError> {
+        let guild_id = owned!(guild_id);
+        self.call("guild.member.list", GuildNextRequest { guild_id, next })
+            .await
+    }
+
+    pub async fn guild_member_kick(
+        &self,
+        guild_id: &str,
+        user_id: &str,
+        permanent: Option<bool>,
+    ) -> Result<(), CallApiError> {
+        let (guild_id, user_id) = owned!(guild_id, user_id);
+        self.call(
+            "guild.member.kick",
+            GuildMemberKickRequest {
+                guild_id,
+                user_id,
+                permanent,
+            },
+        )
+        .await
+    }
+
+    pub async fn guild_member_mute(
+        &self,
+        guild_id: &str,
+        user_id: &str,
+        duration: u64,
+    ) -> Result<(), CallApiError> {
+        let (guild_id, user_id) = owned!(guild_id, user_id);
+        self.call(
+            "guild.member.mute",
+            GuildMemberMuteRequest {
+                guild_id,
+                user_id,
+                duration,
+            },
+        )
+        .await
+    }
+
+    pub async fn guild_member_approve(
+        &self,
+        message_id: &str,
+        approve: bool,
+        comment: Option<String>,
+    ) -> Result<(), CallApiError> {
+        let message_id = owned!(message_id);
+        self.call(
+            "guild.member.approve",
+            ApproveRequest {
+                message_id,
+                approve,
+                comment,
+            },
+        )
+        .await
+    }
+
+    pub async fn guild_member_role_set(
+        &self,
+        guild_id: &str,
+        user_id: &str,
+        role_id: &str,
+    ) -> Result<(), CallApiError> {
+        let (guild_id, user_id, role_id) = owned!(guild_id, user_id, role_id);
+        self.call(
+            "guild.member.role.set",
+            GuildMemberRoleRequest {
+                guild_id,
+                user_id,
+                role_id,
+            },
+        )
+        .await
+    }
+
+    pub async fn guild_member_role_unset(
+        &self,
+        guild_id: &str,
+        user_id: &str,
+        role_id: &str,
+    ) -> Result<(), CallApiError> {
+        let (guild_id, user_id, role_id) = owned!(guild_id, user_id, role_id);
+        self.call(
+            "guild.member.role.unset",
+            GuildMemberRoleRequest {
+                guild_id,
+                user_id,
+                role_id,
+            },
+        )
+        .await
+    }
+
+    // guild.role
+
+    pub async fn guild_role_list(
+        &self,
+        guild_id: &str,
+        next: Option<String>,
+    ) -> Result<List<GuildRole>, CallApiError> {
+        let guild_id = owned!(guild_id);
+        self.call("guild.role.list", GuildNextRequest { guild_id, next })
+            .await
+    }
+
+    pub async fn guild_role_create(
+        &self,
+        guild_id: &str,
+        role: GuildRoleData,
+    ) -> Result<GuildRole, CallApiError> {
+        let guild_id = owned!(guild_id);
+        self.call(
+            "guild.role.create",
+            GuildRoleCreateRequest { guild_id, role },
+        )
+        .await
+    }
+
+    pub async fn guild_role_update(
+        &self,
+        guild_id: &str,
+        role_id: &str,
+        role: GuildRoleData,
+    ) -> Result<(), CallApiError> {
+        let (guild_id, role_id) = owned!(guild_id, role_id);
+        self.call(
+            "guild.role.update",
+            GuildRoleUpdateRequest {
+                guild_id,
+                role_id,
+                role,
+            },
+        )
+        .await
+    }
+
+    pub async fn guild_role_delete(
+        &self,
+        guild_id: &str,
+        role_id: &str,
+    ) -> Result<(), CallApiError> {
+        let (guild_id, role_id) = owned!(guild_id, role_id);
+        self.call(
+            "guild.role.delete",
+            GuildRoleDeleteRequest { guild_id, role_id },
+        )
+        .await
+    }
+
+    // login
+
+    pub async fn login_get(&self) -> Result<Login, CallApiError> {
+        self.call("login.get", serde_json::json!({})).await
+    }
+
+    // message
+
+    pub async fn message_create(
+        &self,
+        channel_id: &str,
+        content: &str,
+    ) -> Result<Vec<Message>, CallApiError> {
+        let (channel_id, content) = owned!(channel_id, content);
+        self.call(
+            "message.create",
+            MessageCreateRequest {
+                channel_id,
+                content,
+            },
+        )
+        .await
+    }
+
+    pub async fn message_get(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<Message, CallApiError> {
+        let (channel_id, message_id) = owned!(channel_id, message_id);
+        self.call(
+            "message.get",
+            MessageIdRequest {
+                channel_id,
+                message_id,
+            },
+        )
+        .await
+    }
+
+    pub async fn message_delete(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<(), CallApiError> {
+        let (channel_id, message_id) = owned!(channel_id, message_id);
+        self.call(
+            "message.delete",
+            MessageIdRequest {
+                channel_id,
+                message_id,
+            },
+        )
+        .await
+    }
+
+    pub async fn message_update(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        content: &str,
+    ) -> Result<(), CallApiError> {
+        let (channel_id, message_id, content) = owned!(channel_id, message_id, content);
+        self.call(
+            "message.update",
+            MessageUpdateRequest {
+                channel_id,
+                message_id,
+                content,
+            },
+        )
+        .await
+    }
+
+    pub async fn message_list(
+        &self,
+        request: MessageListRequest,
+    ) -> Result<BidiList<Message>, CallApiError> {
+        self.call("message.list", request).await
+    }
+
+    // reaction
+
+    pub async fn reaction_create(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), CallApiError> {
+        let (channel_id, message_id, emoji) = owned!(channel_id, message_id, emoji);
+        self.call(
+            "reaction.create",
+            ReactionRequest {
+                channel_id,
+                message_id,
+                emoji,
+            },
+        )
+        .await
+    }
+
+    pub async fn reaction_delete(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &str,
+        user_id: Option<String>,
+    ) -> Result<(), CallApiError> {
+        let (channel_id, message_id, emoji) = owned!(channel_id, message_id, emoji);
+        self.call(
+            "reaction.delete",
+            ReactionDeleteRequest {
+                channel_id,
+                message_id,
+                emoji,
+                user_id,
+            },
+        )
+        .await
+    }
+
+    pub async fn reaction_clear(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: Option<String>,
+    ) -> Result<(), CallApiError> {
+        let (channel_id, message_id) = owned!(channel_id, message_id);
+        self.call(
+            "reaction.clear",
+            ReactionClearRequest {
+                channel_id,
+                message_id,
+                emoji,
+            },
+        )
+        .await
+    }
+
+    pub async fn reaction_list(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &str,
+        next: Option<String>,
+    ) -> Result<List<User>, CallApiError> {
+        let (channel_id, message_id, emoji) = owned!(channel_id, message_id, emoji);
+        self.call(
+            "reaction.list",
+            ReactionListRequest {
+                channel_id,
+                message_id,
+                emoji,
+                next,
+            },
+        )
+        .await
+    }
+
+    // upload
+
+    pub async fn upload_create(
+        &self,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        self.s.upload_files(self.id, files).await
+    }
+
+    // user
+
+    pub async fn user_get(&self, user_id: &str) -> Result<User, CallApiError> {
+        let user_id = owned!(user_id);
+        self.call("user.get", UserIdRequest { user_id }).await
+    }
+
+    // friend
+
+    pub async fn friend_list(&self, next: Option<String>) -> Result<List<User>, CallApiError> {
+        self.call("friend.list", NextRequest { next }).await
+    }
+
+    pub async fn friend_approve(
+        &self,
+        message_id: &str,
+        approve: bool,
+        comment: Option<String>,
+    ) -> Result<(), CallApiError> {
+        let message_id = owned!(message_id);
+        self.call(
+            "friend.approve",
+            ApproveRequest {
+                message_id,
+                approve,
+                comment,
+            },
+        )
+        .await
+    }
+
+    // pagination
+
+    pub fn channels(
+        &self,
+        guild_id: &str,
+    ) -> impl Stream<Item = Result<Channel, CallApiError>> + 'a {
+        let (bot, guild_id) = (*self, owned!(guild_id));
+        paginate(move |next| {
+            let guild_id = guild_id.clone();
+            async move { bot.channel_list(&guild_id, next).await }
+        })
+    }
+
+    pub fn guilds(&self) -> impl Stream<Item = Result<Guild, CallApiError>> + 'a {
+        let bot = *self;
+        paginate(move |next| async move { bot.guild_list(next).await })
+    }
+
+    pub fn guild_members(
+        &self,
+        guild_id: &str,
+    ) -> impl Stream<Item = Result<GuildMember, CallApiError>> + 'a {
+        let (bot, guild_id) = (*self, owned!(guild_id));
+        paginate(move |next| {
+            let guild_id = guild_id.clone();
+            async move { bot.guild_member_list(&guild_id, next).await }
+        })
+    }
+
+    pub fn guild_roles(
+        &self,
+        guild_id: &str,
+    ) -> impl Stream<Item = Result<GuildRole, CallApiError>> + 'a {
+        let (bot, guild_id) = (*self, owned!(guild_id));
+        paginate(move |next| {
+            let guild_id = guild_id.clone();
+            async move { bot.guild_role_list(&guild_id, next).await }
+        })
+    }
+
+    /// Walks messages from `request.next` in `request.direction`, which
+    /// defaults to `Before` like the API does.
+    pub fn messages(
+        &self,
+        request: MessageListRequest,
+    ) -> impl Stream<Item = Result<Message, CallApiError>> + 'a {
+        let bot = *self;
+        let direction = request.direction.unwrap_or(Direction::Before);
+        let mut first = Some(request.next.clone());
+        paginate_bidi(direction, move |next| {
+            let request = MessageListRequest {
+                next: first.take().unwrap_or(next),
+                ..request.clone()
+            };
+            async move { bot.message_list(request).await }
+        })
+    }
+
+    pub fn reactions(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> impl Stream<Item = Result<User, CallApiError>> + 'a {
+        let bot = *self;
+        let (channel_id, message_id, emoji) = owned!(channel_id, message_id, emoji);
+        paginate(move |next| {
+            let (channel_id, message_id, emoji) =
+                (channel_id.clone(), message_id.clone(), emoji.clone());
+            async move {
+                bot.reaction_list(&channel_id, &message_id, &emoji, next)
+                    .await
+            }
+        })
+    }
+
+    pub fn friends(&self) -> impl Stream<Item = Result<User, CallApiError>> + 'a {
+        let bot = *self;
+        paginate(move |next| async move { bot.friend_list(next).await })
+    }
+}
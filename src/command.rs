@@ -0,0 +1,612 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::element::{self, Element};
+use crate::filter::Filter;
+use crate::{AppT, Event, Satori, SdkT, Session, SATORI};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    /// One word, or a quoted string.
+    String,
+    Int,
+    /// A user mentioned with `<at>`, or given by id.
+    User,
+    /// Everything left in the message, elements included.
+    Rest,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgValue {
+    String(String),
+    Int(i64),
+    User(String),
+}
+
+#[derive(Clone, Debug)]
+struct ArgSpec {
+    name: String,
+    kind: ArgKind,
+    optional: bool,
+}
+
+type Handler =
+    Arc<dyn Fn(CommandContext) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+pub struct Command {
+    name: String,
+    aliases: Vec<String>,
+    description: String,
+    args: Vec<ArgSpec>,
+    subcommands: Vec<Command>,
+    handler: Option<Handler>,
+    filter: Option<Filter>,
+}
+
+impl Command {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            aliases: vec![],
+            description: String::new(),
+            args: vec![],
+            subcommands: vec![],
+            handler: None,
+            filter: None,
+        }
+    }
+
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn arg(self, name: impl Into<String>, kind: ArgKind) -> Self {
+        self.push_arg(name.into(), kind, false)
+    }
+
+    pub fn optional_arg(self, name: impl Into<String>, kind: ArgKind) -> Self {
+        self.push_arg(name.into(), kind, true)
+    }
+
+    fn push_arg(mut self, name: String, kind: ArgKind, optional: bool) -> Self {
+        self.args.push(ArgSpec {
+            name,
+            kind,
+            optional,
+        });
+        self
+    }
+
+    pub fn subcommand(mut self, command: Command) -> Self {
+        self.subcommands.push(command);
+        self
+    }
+
+    pub fn handler<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(CommandContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.handler = Some(Arc::new(move |ctx| Box::pin(handler(ctx))));
+        self
+    }
+
+    /// Runs the command, and its subcommands, only for events matching
+    /// `filter`. Other events see no such command.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    fn allows(&self, event: &Event) -> bool {
+        match &self.filter {
+            Some(f) => f.matches(event),
+            None => true,
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+
+    fn usage(&self, path: &[String]) -> String {
+        let mut usage = path.join(" ");
+        for arg in &self.args {
+            let rest = if arg.kind == ArgKind::Rest { "..." } else { "" };
+            let (l, r) = if arg.optional { ('[', ']') } else { ('<', '>') };
+            usage.push_str(&format!(" {l}{}{rest}{r}", arg.name));
+        }
+        usage
+    }
+
+    fn help(&self, prefix: &str, path: &[String]) -> String {
+        let mut help = format!("{prefix}{}", self.usage(path));
+        if !self.description.is_empty() {
+            help.push_str(&format!("\n{}", self.description));
+        }
+        if !self.aliases.is_empty() {
+            help.push_str(&format!("\naliases: {}", self.aliases.join(", ")));
+        }
+        for sub in &self.subcommands {
+            let mut path = path.to_vec();
+            path.push(sub.name.clone());
+            help.push_str(&format!("\n  {prefix}{}", sub.usage(&path)));
+            if !sub.description.is_empty() {
+                help.push_str(&format!(" - {}", sub.description));
+            }
+        }
+        help
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    values: HashMap<String, ArgValue>,
+    /// Options of an `interaction/command`, empty for text commands.
+    pub options: HashMap<String, Value>,
+}
+
+impl Args {
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.values.get(name)
+    }
+
+    pub fn str(&self, name: &str) -> Option<&str> {
+        match self.values.get(name)? {
+            ArgValue::String(s) | ArgValue::User(s) => Some(s),
+            ArgValue::Int(_) => None,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.values.get(name)? {
+            ArgValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn user(&self, name: &str) -> Option<&str> {
+        match self.values.get(name)? {
+            ArgValue::User(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+pub struct CommandContext {
+    pub session: Session,
+    /// Names from the top level command down to the invoked one.
+    pub command: Vec<String>,
+    pub args: Args,
+}
+
+impl Deref for CommandContext {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+
+/// An app running commands from `message-created` and `interaction/command`
+/// events.
+///
+/// A message is a command when it starts with a mention of the bot or one of
+/// the prefixes, or with anything at all when no prefix is set.
+pub struct Commands {
+    prefixes: Vec<String>,
+    mention: bool,
+    help: bool,
+    commands: Vec<Command>,
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum Outcome<'c> {
+    Run(&'c Command, Vec<String>, Args),
+    Reply(String),
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self {
+            prefixes: vec![],
+            mention: true,
+            help: true,
+            commands: vec![],
+        }
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    /// Whether a leading mention of the bot triggers commands, on by default.
+    pub fn mention(mut self, enabled: bool) -> Self {
+        self.mention = enabled;
+        self
+    }
+
+    /// Whether `help` lists the commands, on by default.
+    pub fn help(mut self, enabled: bool) -> Self {
+        self.help = enabled;
+        self
+    }
+
+    pub fn command(mut self, command: Command) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    fn display_prefix(&self) -> &str {
+        self.prefixes
+            .first()
+            .map(|p| p.as_str())
+            .unwrap_or_default()
+    }
+
+    fn help_text(&self) -> String {
+        let prefix = self.display_prefix();
+        let mut lines = vec![];
+        for command in &self.commands {
+            let mut line = format!(
+                "{prefix}{}",
+                command.usage(std::slice::from_ref(&command.name))
+            );
+            if !command.description.is_empty() {
+                line.push_str(&format!(" - {}", command.description));
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    fn is_help(&self, name: &str) -> bool {
+        self.help && name == "help" && !self.commands.iter().any(|c| c.matches(name))
+    }
+
+    fn route_message<'c>(&'c self, event: &Event) -> Option<Outcome<'c>> {
+        let message = event.message.as_ref()?;
+        if event.user.as_ref().is_some_and(|u| u.id == event.self_id) {
+            return None;
+        }
+        let elements = element::parse(&message.content);
+        let mut sc = Scanner::new(&elements);
+        let mentioned = self.mention && sc.strip_mention(&event.self_id);
+        let prefixed = self.prefixes.iter().any(|p| sc.strip_prefix(p));
+        if !mentioned && !prefixed && !self.prefixes.is_empty() {
+            return None;
+        }
+        let Some(Token::Word(name)) = sc.next() else {
+            return None;
+        };
+        if self.is_help(&name) {
+            return Some(Outcome::Reply(self.help_for(sc.words())));
+        }
+        let mut command = self.commands.iter().find(|c| c.matches(&name))?;
+        if !command.allows(event) {
+            return None;
+        }
+        let mut path = vec![command.name.clone()];
+        loop {
+            let mut look = sc;
+            match look.next() {
+                Some(Token::Word(w)) => match command.subcommands.iter().find(|c| c.matches(&w)) {
+                    Some(sub) if !sub.allows(event) => return None,
+                    Some(sub) => {
+                        command = sub;
+                        path.push(sub.name.clone());
+                        sc = look;
+                    }
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        Some(self.outcome(command, path, |spec| match spec.kind {
+            ArgKind::Rest => {
+                let rest = sc.rest();
+                (!rest.is_empty()).then_some(Token::Word(rest))
+            }
+            _ => sc.next(),
+        }))
+    }
+
+    fn route_argv<'c>(&'c self, event: &Event) -> Option<Outcome<'c>> {
+        let argv = event.argv.as_ref()?;
+        let mut names = argv
+            .name
+            .split(|c: char| c == '.' || c.is_whitespace())
+            .filter(|n| !n.is_empty());
+        let name = names.next()?;
+        if self.is_help(name) {
+            return Some(Outcome::Reply(
+                self.help_for(argv.arguments.iter().map(value_text).collect()),
+            ));
+        }
+        let mut command = self.commands.iter().find(|c| c.matches(name))?;
+        let mut path = vec![command.name.clone()];
+        for name in names {
+            if !command.allows(event) {
+                return None;
+            }
+            command = command.subcommands.iter().find(|c| c.matches(name))?;
+            path.push(command.name.clone());
+        }
+        if !command.allows(event) {
+            return None;
+        }
+        let mut arguments = argv.arguments.iter();
+        let mut outcome = self.outcome(command, path, |spec| match spec.kind {
+            ArgKind::Rest => {
+                let rest: Vec<_> = arguments.by_ref().map(value_text).collect();
+                (!rest.is_empty()).then(|| Token::Word(rest.join(" ")))
+            }
+            _ => arguments.next().map(value_token),
+        });
+        if let Outcome::Run(_, _, args) = &mut outcome {
+            args.options = argv.options.clone();
+        }
+        Some(outcome)
+    }
+
+    fn outcome<'c>(
+        &self,
+        command: &'c Command,
+        path: Vec<String>,
+        mut next: impl FnMut(&ArgSpec) -> Option<Token>,
+    ) -> Outcome<'c> {
+        let prefix = self.display_prefix();
+        if command.handler.is_none() {
+            return Outcome::Reply(command.help(prefix, &path));
+        }
+        let mut args = Args::default();
+        for spec in &command.args {
+            let value = match next(spec) {
+                Some(token) => convert(spec, token),
+                None if spec.optional => continue,
+                None => Err(format!("missing argument <{}>", spec.name)),
+            };
+            match value {
+                Ok(value) => {
+                    args.values.insert(spec.name.clone(), value);
+                }
+                Err(e) => {
+                    return Outcome::Reply(format!("{e}\nusage: {prefix}{}", command.usage(&path)))
+                }
+            }
+        }
+        Outcome::Run(command, path, args)
+    }
+
+    fn help_for(&self, names: Vec<String>) -> String {
+        let Some((name, subs)) = names.split_first() else {
+            return self.help_text();
+        };
+        let Some(mut command) = self.commands.iter().find(|c| c.matches(name)) else {
+            return format!("unknown command {name}");
+        };
+        let mut path = vec![command.name.clone()];
+        for name in subs {
+            match command.subcommands.iter().find(|c| c.matches(name)) {
+                Some(sub) => {
+                    command = sub;
+                    path.push(sub.name.clone());
+                }
+                None => break,
+            }
+        }
+        command.help(self.display_prefix(), &path)
+    }
+}
+
+#[async_trait]
+impl AppT for Commands {
+    type Config = ();
+
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, _config: ()) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        vec![]
+    }
+
+    async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let outcome = match event.ty.as_str() {
+            "message-created" => self.route_message(&event),
+            "interaction/command" => self.route_argv(&event),
+            _ => None,
+        };
+        let Some(outcome) = outcome else {
+            return;
+        };
+        let session = Session::new(s, event);
+        match outcome {
+            Outcome::Run(command, path, args) => {
+                let Some(handler) = &command.handler else {
+                    return;
+                };
+                handler(CommandContext {
+                    session,
+                    command: path,
+                    args,
+                })
+                .await
+            }
+            Outcome::Reply(content) => {
+                let content = element::escape(&content, false);
+                if let Err(e) = session.reply(&content).await {
+                    warn!(target: SATORI, "reply to command failed: {e}");
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    User(String),
+    Other,
+}
+
+fn convert(spec: &ArgSpec, token: Token) -> Result<ArgValue, String> {
+    match (spec.kind, token) {
+        (ArgKind::Int, Token::Word(w)) => w
+            .parse()
+            .map(ArgValue::Int)
+            .map_err(|_| format!("argument <{}> must be an integer", spec.name)),
+        (ArgKind::User, Token::Word(id) | Token::User(id)) => Ok(ArgValue::User(id)),
+        (ArgKind::String | ArgKind::Rest, Token::Word(w)) => Ok(ArgValue::String(w)),
+        _ => Err(format!("argument <{}> is invalid", spec.name)),
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn value_token(value: &Value) -> Token {
+    let text = value_text(value);
+    match element::parse(&text).as_slice() {
+        [Element::At(at)] if at.id.is_some() => Token::User(at.id.clone().unwrap_or_default()),
+        _ => Token::Word(text),
+    }
+}
+
+/// Reads words and elements off a parsed message.
+#[derive(Clone, Copy)]
+struct Scanner<'a> {
+    elements: &'a [Element],
+    idx: usize,
+    // byte offset into the current text element
+    off: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(elements: &'a [Element]) -> Self {
+        Self {
+            elements,
+            idx: 0,
+            off: 0,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.idx += 1;
+        self.off = 0;
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(el) = self.elements.get(self.idx) {
+            match el {
+                Element::Text(t) => {
+                    let rest = &t[self.off..];
+                    self.off += rest.len() - rest.trim_start().len();
+                    if self.off < t.len() {
+                        return;
+                    }
+                    self.advance();
+                }
+                Element::Br => self.advance(),
+                _ => return,
+            }
+        }
+    }
+
+    fn strip_mention(&mut self, self_id: &str) -> bool {
+        self.skip_ws();
+        match self.elements.get(self.idx) {
+            Some(Element::At(at)) if at.id.as_deref() == Some(self_id) => {
+                self.advance();
+                self.skip_ws();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn strip_prefix(&mut self, prefix: &str) -> bool {
+        self.skip_ws();
+        match self.elements.get(self.idx) {
+            Some(Element::Text(t)) if t[self.off..].starts_with(prefix) => {
+                self.off += prefix.len();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.skip_ws();
+        let token = match self.elements.get(self.idx)? {
+            Element::Text(t) => {
+                let rest = &t[self.off..];
+                let (word, len) = match rest.strip_prefix('"') {
+                    Some(quoted) => match quoted.find('"') {
+                        Some(end) => (&quoted[..end], end + 2),
+                        None => (quoted, rest.len()),
+                    },
+                    None => {
+                        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                        (&rest[..end], end)
+                    }
+                };
+                self.off += len;
+                return Some(Token::Word(word.to_owned()));
+            }
+            Element::At(at) => at.id.clone().map(Token::User).unwrap_or(Token::Other),
+            _ => Token::Other,
+        };
+        self.advance();
+        Some(token)
+    }
+
+    fn words(mut self) -> Vec<String> {
+        let mut words = vec![];
+        while let Some(token) = self.next() {
+            if let Token::Word(w) = token {
+                words.push(w);
+            }
+        }
+        words
+    }
+
+    fn rest(&mut self) -> String {
+        self.skip_ws();
+        let mut rest = vec![];
+        if let Some(Element::Text(t)) = self.elements.get(self.idx) {
+            rest.push(Element::text(&t[self.off..]));
+            self.advance();
+        }
+        rest.extend(self.elements.iter().skip(self.idx).cloned());
+        self.idx = self.elements.len();
+        element::stringify(&rest).trim_end().to_owned()
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::{mpsc, Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::{error, warn};
+
+use crate::{AppT, Event, Satori, SdkT, SATORI};
+
+/// Which events must be handled one after another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DispatchKey {
+    /// No ordering, every event is handled as soon as a permit is free.
+    None,
+    /// Events of the same channel are handled in order.
+    #[default]
+    Channel,
+    /// Events of the same user are handled in order.
+    User,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DispatchConfig {
+    /// Events handled at the same time, 0 for no limit.
+    pub concurrency: usize,
+    pub key: DispatchKey,
+    /// Events waiting per key, later ones are dropped while it is full.
+    pub queue_size: usize,
+    /// Events dispatched but not handled yet, over all keys. `dispatch`
+    /// waits and `try_dispatch` fails while it is reached.
+    pub max_pending: usize,
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 64,
+            key: DispatchKey::Channel,
+            queue_size: 256,
+            max_pending: 4096,
+        }
+    }
+}
+
+/// Returned by `try_dispatch` with the event while the dispatcher is full.
+#[derive(Debug)]
+pub struct DispatchFull(pub Box<Event>);
+
+impl std::fmt::Display for DispatchFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "dispatcher is full, event {} not dispatched", self.0.sn)
+    }
+}
+
+impl std::error::Error for DispatchFull {}
+
+/// Hands events to the app of a `Satori` with bounded concurrency, keeping
+/// the order of events sharing a key. Events without a key, such as guild
+/// events when ordering by channel, are not ordered. A dispatcher serves a
+/// single `Satori`.
+///
+/// At most `max_pending` events are held at once, `dispatch` waits for room
+/// beyond that. Events run through the event middleware and are offered to
+/// prompts in the order they arrive, so the answer to a prompt is not queued
+/// behind the handler waiting for it. Handlers are only spawned once they
+/// got a permit: each key is served by one worker while its queue is not
+/// empty, and a full queue only drops events of its own key.
+#[derive(Clone)]
+pub struct Dispatcher {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: DispatchConfig,
+    permits: Option<Arc<Semaphore>>,
+    pending: Arc<Semaphore>,
+    intake: Mutex<Option<mpsc::Sender<Pending>>>,
+    queues: Mutex<HashMap<String, VecDeque<Pending>>>,
+    ready: Mutex<VecDeque<Job>>,
+    notify: Notify,
+}
+
+/// An event holding its place among the pending ones until it is handled.
+struct Pending {
+    event: Event,
+    slot: OwnedSemaphorePermit,
+}
+
+/// Work waiting for a permit.
+enum Job {
+    Single(Box<Pending>),
+    Key(String),
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new(DispatchConfig::default())
+    }
+}
+
+impl Dispatcher {
+    pub fn new(config: DispatchConfig) -> Self {
+        let permits =
+            (config.concurrency > 0).then(|| Arc::new(Semaphore::new(config.concurrency)));
+        let pending = Arc::new(Semaphore::new(config.max_pending.max(1)));
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                permits,
+                pending,
+                intake: Default::default(),
+                queues: Default::default(),
+                ready: Default::default(),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    fn key(&self, event: &Event) -> Option<String> {
+        let id = match self.inner.config.key {
+            DispatchKey::None => return None,
+            DispatchKey::Channel => &event.channel.as_ref()?.id,
+            DispatchKey::User => &event.user.as_ref()?.id,
+        };
+        Some(format!("{}/{}/{id}", event.platform, event.self_id))
+    }
+
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let permits = self.inner.permits.clone()?;
+        permits.acquire_owned().await.ok()
+    }
+
+    /// Dispatches `event`, waiting while `max_pending` events are held.
+    pub async fn dispatch<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        // never closed
+        let Ok(slot) = self.inner.pending.clone().acquire_owned().await else {
+            return;
+        };
+        self.send(s, Pending { event, slot });
+    }
+
+    /// Dispatches `event` unless `max_pending` events are held.
+    pub fn try_dispatch<S, A>(
+        &self,
+        s: &Arc<Satori<S, A>>,
+        event: Event,
+    ) -> Result<(), DispatchFull>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let Ok(slot) = self.inner.pending.clone().try_acquire_owned() else {
+            return Err(DispatchFull(Box::new(event)));
+        };
+        self.send(s, Pending { event, slot });
+        Ok(())
+    }
+
+    fn send<S, A>(&self, s: &Arc<Satori<S, A>>, mut pending: Pending)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let mut intake = self.inner.intake.lock().unwrap();
+        if let Some(tx) = intake.as_ref() {
+            // there is room, as every pending event holds a slot
+            match tx.try_send(pending) {
+                Ok(()) => return,
+                // the intake task is gone, start it over
+                Err(e) => pending = e.into_inner(),
+            }
+        }
+        let (tx, rx) = mpsc::channel(self.inner.config.max_pending.max(1));
+        tx.try_send(pending).ok();
+        tokio::spawn(self.clone().intake(Arc::downgrade(s), rx));
+        if intake.is_none() {
+            tokio::spawn(self.clone().launch(Arc::downgrade(s)));
+        }
+        *intake = Some(tx);
+    }
+
+    // holds the `Satori` weakly, as the `Satori` usually holds the dispatcher
+    async fn intake<S, A>(self, s: Weak<Satori<S, A>>, mut rx: mpsc::Receiver<Pending>)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        while let Some(Pending { event, slot }) = rx.recv().await {
+            let Some(s) = s.upgrade() else {
+                return;
+            };
+            let sn = event.sn;
+            let filtered = tokio::spawn(async move { s.filter_event(event).await });
+            match filtered.await {
+                Ok(Some(event)) => self.queue(Pending { event, slot }),
+                Ok(None) => {}
+                Err(e) => error!(target: SATORI, "middleware of event {sn} failed: {e}"),
+            }
+        }
+    }
+
+    fn queue(&self, pending: Pending) {
+        let job = match self.key(&pending.event) {
+            None => Job::Single(Box::new(pending)),
+            Some(key) => {
+                let mut queues = self.inner.queues.lock().unwrap();
+                match queues.get_mut(&key) {
+                    Some(queue) if queue.len() >= self.inner.config.queue_size.max(1) => {
+                        let sn = pending.event.sn;
+                        warn!(target: SATORI, "queue of {key} is full, drop event {sn}");
+                        return;
+                    }
+                    Some(queue) => {
+                        queue.push_back(pending);
+                        return;
+                    }
+                    None => {
+                        queues.insert(key.clone(), VecDeque::from([pending]));
+                        Job::Key(key)
+                    }
+                }
+            }
+        };
+        self.inner.ready.lock().unwrap().push_back(job);
+        self.inner.notify.notify_one();
+    }
+
+    // spawns each job once it got a permit
+    async fn launch<S, A>(self, s: Weak<Satori<S, A>>)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        loop {
+            let permit = self.acquire().await;
+            let job = loop {
+                let job = self.inner.ready.lock().unwrap().pop_front();
+                match job {
+                    Some(job) => break job,
+                    None => self.inner.notify.notified().await,
+                }
+            };
+            let Some(s) = s.upgrade() else {
+                return;
+            };
+            tokio::spawn(self.clone().work(s, job, permit));
+        }
+    }
+
+    // handles a single event, or the queue of a key until it is empty
+    async fn work<S, A>(self, s: Arc<Satori<S, A>>, job: Job, _permit: Option<OwnedSemaphorePermit>)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let key = match job {
+            Job::Single(pending) => return handle(&s, *pending).await,
+            Job::Key(key) => key,
+        };
+        loop {
+            let next = {
+                let mut queues = self.inner.queues.lock().unwrap();
+                match queues.get_mut(&key).and_then(|q| q.pop_front()) {
+                    Some(next) => next,
+                    None => {
+                        queues.remove(&key);
+                        return;
+                    }
+                }
+            };
+            handle(&s, next).await;
+        }
+    }
+}
+
+async fn handle<S, A>(s: &Arc<Satori<S, A>>, Pending { event, slot }: Pending)
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let sn = event.sn;
+    // a panicking handler only loses its own event
+    let handled = tokio::spawn({
+        let s = s.clone();
+        async move { s.a.handle_event(&s, event).await }
+    });
+    if let Err(e) = handled.await {
+        error!(target: SATORI, "handler of event {sn} failed: {e}");
+    }
+    drop(slot);
+}
@@ -0,0 +1,346 @@
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use serde_json::Value;
+use std::any::{type_name, Any};
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use tokio::task::JoinHandle;
+use tracing::error;
+
+use crate::multi::{bot_not_found, not_proxied, owns, proxies};
+use crate::{
+    AppT, BotId, CallApiError, Event, Login, ProxyResource, Satori, SdkT, UploadFile, SATORI,
+};
+
+/// Config handed to a [`DynSdk`] or [`DynApp`], it must hold the `Config` of
+/// the underlying [`SdkT`] or [`AppT`].
+pub type DynConfig = Box<dyn Any + Send>;
+
+/// A `Satori` whose sdk and app are picked at runtime, built by
+/// [`DynSatori::new_dyn`].
+pub type DynSatori = Satori<Plugin<dyn DynSdk>, Plugin<dyn DynApp>>;
+
+/// The boxed sdk or app of a [`DynSatori`]. It can only be built by
+/// [`DynSatori::new_dyn`], so it never runs in any other `Satori`.
+pub struct Plugin<T: ?Sized> {
+    inner: Box<T>,
+    satori: Weak<DynSatori>,
+}
+
+impl DynSatori {
+    pub fn new_dyn(sdk: Box<dyn DynSdk>, app: Box<dyn DynApp>) -> Arc<Self> {
+        Arc::new_cyclic(|satori| Self {
+            s: Plugin {
+                inner: sdk,
+                satori: satori.clone(),
+            },
+            a: Plugin {
+                inner: app,
+                satori: satori.clone(),
+            },
+            stx: tokio::sync::broadcast::channel(4).0,
+            prompts: Default::default(),
+        })
+    }
+}
+
+impl<T: ?Sized> Plugin<T> {
+    // the generic methods of the static traits are always called with the
+    // `DynSatori` holding this plugin, which is alive while they run
+    fn satori(&self) -> Arc<DynSatori> {
+        self.satori
+            .upgrade()
+            .expect("plugin outlived its DynSatori")
+    }
+}
+
+/// Object safe counterpart of [`SdkT`], implemented for every `SdkT`.
+#[async_trait]
+pub trait DynSdk: Send + Sync {
+    async fn start(&self, s: &Arc<DynSatori>, config: DynConfig) -> Vec<JoinHandle<()>>;
+    async fn call_api(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError>;
+    async fn get_logins(&self) -> Vec<Login>;
+    async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError>;
+    async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError>;
+    async fn call_internal(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError>;
+    async fn on_shutdown(&self, s: &Arc<DynSatori>);
+}
+
+/// Object safe counterpart of [`AppT`], implemented for every `AppT`.
+#[async_trait]
+pub trait DynApp: Send + Sync {
+    async fn start(&self, s: &Arc<DynSatori>, config: DynConfig) -> Vec<JoinHandle<()>>;
+    async fn handle_event(&self, s: &Arc<DynSatori>, event: Event);
+    async fn filter_event(&self, s: &Arc<DynSatori>, event: Event) -> Option<Event>;
+    async fn on_shutdown(&self, s: &Arc<DynSatori>);
+}
+
+fn downcast_config<T: 'static>(config: DynConfig) -> Option<T> {
+    match config.downcast::<T>() {
+        Ok(config) => Some(*config),
+        Err(_) => {
+            error!(target: SATORI, "config is not a {}", type_name::<T>());
+            None
+        }
+    }
+}
+
+#[async_trait]
+impl<T> DynSdk for T
+where
+    T: SdkT + Send + Sync,
+    T::Config: Send + 'static,
+{
+    async fn start(&self, s: &Arc<DynSatori>, config: DynConfig) -> Vec<JoinHandle<()>> {
+        match downcast_config::<T::Config>(config) {
+            Some(config) => SdkT::start(self, s, config).await,
+            None => vec![],
+        }
+    }
+    async fn call_api(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError> {
+        SdkT::call_api(self, api, bot, data).await
+    }
+    async fn get_logins(&self) -> Vec<Login> {
+        SdkT::get_logins(self).await
+    }
+    async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        SdkT::upload(self, bot, files).await
+    }
+    async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+        SdkT::fetch_resource(self, url).await
+    }
+    async fn call_internal(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        SdkT::call_internal(self, method, bot, data).await
+    }
+    async fn on_shutdown(&self, s: &Arc<DynSatori>) {
+        SdkT::on_shutdown(self, s).await
+    }
+}
+
+#[async_trait]
+impl<T> DynApp for T
+where
+    T: AppT + Send + Sync,
+    T::Config: Send + 'static,
+{
+    async fn start(&self, s: &Arc<DynSatori>, config: DynConfig) -> Vec<JoinHandle<()>> {
+        match downcast_config::<T::Config>(config) {
+            Some(config) => AppT::start(self, s, config).await,
+            None => vec![],
+        }
+    }
+    async fn handle_event(&self, s: &Arc<DynSatori>, event: Event) {
+        AppT::handle_event(self, s, event).await
+    }
+    async fn filter_event(&self, s: &Arc<DynSatori>, event: Event) -> Option<Event> {
+        AppT::filter_event(self, s, event).await
+    }
+    async fn on_shutdown(&self, s: &Arc<DynSatori>) {
+        AppT::on_shutdown(self, s).await
+    }
+}
+
+#[async_trait]
+impl SdkT for Plugin<dyn DynSdk> {
+    type Config = DynConfig;
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, config: DynConfig) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.start(&self.satori(), config).await
+    }
+    async fn call_api(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError> {
+        self.inner.call_api(api, bot, data).await
+    }
+    async fn get_logins(&self) -> Vec<Login> {
+        self.inner.get_logins().await
+    }
+    async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        self.inner.upload(bot, files).await
+    }
+    async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+        self.inner.fetch_resource(url).await
+    }
+    async fn call_internal(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        self.inner.call_internal(method, bot, data).await
+    }
+    async fn on_shutdown<S, A>(&self, _s: &Arc<Satori<S, A>>)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.on_shutdown(&self.satori()).await
+    }
+}
+
+#[async_trait]
+impl AppT for Plugin<dyn DynApp> {
+    type Config = DynConfig;
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, config: DynConfig) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.start(&self.satori(), config).await
+    }
+    async fn handle_event<S, A>(&self, _s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.handle_event(&self.satori(), event).await
+    }
+    async fn filter_event<S, A>(&self, _s: &Arc<Satori<S, A>>, event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.filter_event(&self.satori(), event).await
+    }
+    async fn on_shutdown<S, A>(&self, _s: &Arc<Satori<S, A>>)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.on_shutdown(&self.satori()).await
+    }
+}
+
+fn zip_configs<T: ?Sized>(plugins: &[Box<T>], config: DynConfig) -> Vec<(&T, DynConfig)> {
+    let Some(configs) = downcast_config::<Vec<DynConfig>>(config) else {
+        return vec![];
+    };
+    if configs.len() != plugins.len() {
+        error!(
+            target: SATORI,
+            "{} configs for {} plugins",
+            configs.len(),
+            plugins.len()
+        );
+        return vec![];
+    }
+    plugins.iter().map(|p| &**p).zip(configs).collect()
+}
+
+/// Routes every call to the sdk owning the bot, like a tuple of sdks does.
+/// Its config is a `Vec<DynConfig>` with one entry per sdk.
+#[async_trait]
+impl DynSdk for Vec<Box<dyn DynSdk>> {
+    async fn start(&self, s: &Arc<DynSatori>, config: DynConfig) -> Vec<JoinHandle<()>> {
+        let mut joins = vec![];
+        for (sdk, config) in zip_configs(self, config) {
+            joins.extend(sdk.start(s, config).await);
+        }
+        joins
+    }
+    async fn call_api(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError> {
+        for sdk in self.iter().map(|s| &**s) {
+            if owns(&sdk.get_logins().await, bot) {
+                return sdk.call_api(api, bot, data).await;
+            }
+        }
+        Err(bot_not_found(bot))
+    }
+    async fn get_logins(&self) -> Vec<Login> {
+        let mut logins = vec![];
+        for sdk in self.iter().map(|s| &**s) {
+            logins.extend(sdk.get_logins().await);
+        }
+        logins
+    }
+    async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        for sdk in self.iter().map(|s| &**s) {
+            if owns(&sdk.get_logins().await, bot) {
+                return sdk.upload(bot, files).await;
+            }
+        }
+        Err(bot_not_found(bot))
+    }
+    async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+        for sdk in self.iter().map(|s| &**s) {
+            if proxies(&sdk.get_logins().await, url) {
+                return sdk.fetch_resource(url).await;
+            }
+        }
+        Err(not_proxied(url))
+    }
+    async fn call_internal(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        for sdk in self.iter().map(|s| &**s) {
+            if owns(&sdk.get_logins().await, bot) {
+                return sdk.call_internal(method, bot, data).await;
+            }
+        }
+        Err(bot_not_found(bot))
+    }
+    async fn on_shutdown(&self, s: &Arc<DynSatori>) {
+        for sdk in self.iter().map(|s| &**s) {
+            sdk.on_shutdown(s).await;
+        }
+    }
+}
+
+/// Delivers every event to each app, like a tuple of apps does. Its config
+/// is a `Vec<DynConfig>` with one entry per app.
+#[async_trait]
+impl DynApp for Vec<Box<dyn DynApp>> {
+    async fn start(&self, s: &Arc<DynSatori>, config: DynConfig) -> Vec<JoinHandle<()>> {
+        let mut joins = vec![];
+        for (app, config) in zip_configs(self, config) {
+            joins.extend(app.start(s, config).await);
+        }
+        joins
+    }
+    async fn handle_event(&self, s: &Arc<DynSatori>, event: Event) {
+        join_all(self.iter().map(|app| async {
+            if let Some(event) = app.filter_event(s, event.clone()).await {
+                app.handle_event(s, event).await
+            }
+        }))
+        .await;
+    }
+    async fn filter_event(&self, _s: &Arc<DynSatori>, event: Event) -> Option<Event> {
+        Some(event)
+    }
+    async fn on_shutdown(&self, s: &Arc<DynSatori>) {
+        for app in self.iter().map(|a| &**a) {
+            app.on_shutdown(s).await;
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use std::fmt::{self, Display, Write};
+
+pub type Attrs = Vec<(String, Option<String>)>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Element {
+    Text(String),
+    At(At),
+    Sharp(Sharp),
+    A(Link),
+    Img(Resource),
+    Audio(Resource),
+    Video(Resource),
+    File(Resource),
+    B(Vec<Element>),
+    I(Vec<Element>),
+    U(Vec<Element>),
+    S(Vec<Element>),
+    Spl(Vec<Element>),
+    Code(Vec<Element>),
+    Sup(Vec<Element>),
+    Sub(Vec<Element>),
+    Br,
+    P(Vec<Element>),
+    Message(MessageElement),
+    Quote(Quote),
+    Author(Author),
+    Button(Button),
+    /// Any element that does not fit a typed variant, kept as is so that
+    /// `parse` followed by `stringify` never drops content. Alias tags such as
+    /// `strong` or `image` are the only thing normalized on the way.
+    Other(Node),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Node {
+    pub tag: String,
+    pub attrs: Attrs,
+    pub children: Vec<Element>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct At {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub role: Option<String>,
+    pub ty: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sharp {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Link {
+    pub href: String,
+    pub children: Vec<Element>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Resource {
+    pub src: String,
+    pub title: Option<String>,
+    pub cache: Option<bool>,
+    pub timeout: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration: Option<u32>,
+    pub poster: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageElement {
+    pub id: Option<String>,
+    pub forward: bool,
+    pub children: Vec<Element>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quote {
+    pub id: Option<String>,
+    pub children: Vec<Element>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Author {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Button {
+    pub id: Option<String>,
+    pub ty: Option<String>,
+    pub href: Option<String>,
+    pub text: Option<String>,
+    pub theme: Option<String>,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn at(id: impl Into<String>) -> Self {
+        Self::At(At {
+            id: Some(id.into()),
+            ..Default::default()
+        })
+    }
+
+    pub fn quote(id: impl Into<String>) -> Self {
+        Self::Quote(Quote {
+            id: Some(id.into()),
+            children: vec![],
+        })
+    }
+
+    pub fn img(src: impl Into<String>) -> Self {
+        Self::Img(Resource {
+            src: src.into(),
+            ..Default::default()
+        })
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        Some(match self {
+            Self::Text(_) => return None,
+            Self::At(_) => "at",
+            Self::Sharp(_) => "sharp",
+            Self::A(_) => "a",
+            Self::Img(_) => "img",
+            Self::Audio(_) => "audio",
+            Self::Video(_) => "video",
+            Self::File(_) => "file",
+            Self::B(_) => "b",
+            Self::I(_) => "i",
+            Self::U(_) => "u",
+            Self::S(_) => "s",
+            Self::Spl(_) => "spl",
+            Self::Code(_) => "code",
+            Self::Sup(_) => "sup",
+            Self::Sub(_) => "sub",
+            Self::Br => "br",
+            Self::P(_) => "p",
+            Self::Message(_) => "message",
+            Self::Quote(_) => "quote",
+            Self::Author(_) => "author",
+            Self::Button(_) => "button",
+            Self::Other(node) => &node.tag,
+        })
+    }
+
+    pub fn children(&self) -> &[Element] {
+        match self {
+            Self::A(Link { children, .. })
+            | Self::B(children)
+            | Self::I(children)
+            | Self::U(children)
+            | Self::S(children)
+            | Self::Spl(children)
+            | Self::Code(children)
+            | Self::Sup(children)
+            | Self::Sub(children)
+            | Self::P(children)
+            | Self::Message(MessageElement { children, .. })
+            | Self::Quote(Quote { children, .. })
+            | Self::Button(Button { children, .. })
+            | Self::Other(Node { children, .. }) => children,
+            _ => &[],
+        }
+    }
+
+    /// Plain text of this element and its descendants, without any markup.
+    pub fn plain_text(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Br => "\n".to_owned(),
+            _ => self.children().iter().map(Self::plain_text).collect(),
+        }
+    }
+
+    fn into_node(self) -> Node {
+        let tag = self.tag().unwrap_or_default().to_owned();
+        let mut attrs = Attrs::new();
+        let mut push = |k: &str, v: Option<String>| {
+            if let Some(v) = v {
+                attrs.push((k.to_owned(), Some(v)));
+            }
+        };
+        let children = match self {
+            Self::Text(_) | Self::Br => vec![],
+            Self::At(at) => {
+                push("id", at.id);
+                push("name", at.name);
+                push("role", at.role);
+                push("type", at.ty);
+                vec![]
+            }
+            Self::Sharp(sharp) => {
+                push("id", Some(sharp.id));
+                push("name", sharp.name);
+                vec![]
+            }
+            Self::A(link) => {
+                push("href", Some(link.href));
+                link.children
+            }
+            Self::Img(r) | Self::Audio(r) | Self::Video(r) | Self::File(r) => {
+                push("src", Some(r.src));
+                push("title", r.title);
+                push("cache", r.cache.map(|b| b.to_string()));
+                push("timeout", r.timeout.map(|n| n.to_string()));
+                push("width", r.width.map(|n| n.to_string()));
+                push("height", r.height.map(|n| n.to_string()));
+                push("duration", r.duration.map(|n| n.to_string()));
+                push("poster", r.poster);
+                vec![]
+            }
+            Self::B(children)
+            | Self::I(children)
+            | Self::U(children)
+            | Self::S(children)
+            | Self::Spl(children)
+            | Self::Code(children)
+            | Self::Sup(children)
+            | Self::Sub(children)
+            | Self::P(children) => children,
+            Self::Message(message) => {
+                push("id", message.id);
+                if message.forward {
+                    attrs.push(("forward".to_owned(), None));
+                }
+                message.children
+            }
+            Self::Quote(quote) => {
+                push("id", quote.id);
+                quote.children
+            }
+            Self::Author(author) => {
+                push("id", author.id);
+                push("name", author.name);
+                push("avatar", author.avatar);
+                vec![]
+            }
+            Self::Button(button) => {
+                push("id", button.id);
+                push("type", button.ty);
+                push("href", button.href);
+                push("text", button.text);
+                push("theme", button.theme);
+                button.children
+            }
+            Self::Other(node) => return node,
+        };
+        Node {
+            tag,
+            attrs,
+            children,
+        }
+    }
+
+    fn from_node(node: Node) -> Self {
+        typed(&node).unwrap_or(Self::Other(node))
+    }
+}
+
+fn typed(node: &Node) -> Option<Element> {
+    let mut attrs = AttrReader::new(&node.attrs);
+    let children = || node.children.clone();
+    let resource = |attrs: &mut AttrReader| -> Option<Resource> {
+        Some(Resource {
+            src: attrs.required("src")?,
+            title: attrs.string("title")?,
+            cache: attrs.parsed("cache")?,
+            timeout: attrs.parsed("timeout")?,
+            width: attrs.parsed("width")?,
+            height: attrs.parsed("height")?,
+            duration: attrs.parsed("duration")?,
+            poster: attrs.string("poster")?,
+        })
+    };
+    let leaf = node.children.is_empty();
+    let element = match node.tag.as_str() {
+        "at" if leaf => Element::At(At {
+            id: attrs.string("id")?,
+            name: attrs.string("name")?,
+            role: attrs.string("role")?,
+            ty: attrs.string("type")?,
+        }),
+        "sharp" if leaf => Element::Sharp(Sharp {
+            id: attrs.required("id")?,
+            name: attrs.string("name")?,
+        }),
+        "a" => Element::A(Link {
+            href: attrs.required("href")?,
+            children: children(),
+        }),
+        "img" | "image" if leaf => Element::Img(resource(&mut attrs)?),
+        "audio" if leaf => Element::Audio(resource(&mut attrs)?),
+        "video" if leaf => Element::Video(resource(&mut attrs)?),
+        "file" if leaf => Element::File(resource(&mut attrs)?),
+        "b" | "strong" => Element::B(children()),
+        "i" | "em" => Element::I(children()),
+        "u" | "ins" => Element::U(children()),
+        "s" | "del" => Element::S(children()),
+        "spl" => Element::Spl(children()),
+        "code" => Element::Code(children()),
+        "sup" => Element::Sup(children()),
+        "sub" => Element::Sub(children()),
+        "br" if leaf => Element::Br,
+        "p" => Element::P(children()),
+        "message" => Element::Message(MessageElement {
+            id: attrs.string("id")?,
+            forward: attrs.parsed("forward")?.unwrap_or(false),
+            children: children(),
+        }),
+        "quote" => Element::Quote(Quote {
+            id: attrs.string("id")?,
+            children: children(),
+        }),
+        "author" if leaf => Element::Author(Author {
+            id: attrs.string("id")?,
+            name: attrs.string("name")?,
+            avatar: attrs.string("avatar")?,
+        }),
+        "button" => Element::Button(Button {
+            id: attrs.string("id")?,
+            ty: attrs.string("type")?,
+            href: attrs.string("href")?,
+            text: attrs.string("text")?,
+            theme: attrs.string("theme")?,
+            children: children(),
+        }),
+        _ => return None,
+    };
+    // Only keep the typed form when every attribute was understood, so that
+    // serializing it again yields the same markup.
+    attrs.exhausted().then_some(element)
+}
+
+/// Reads attributes for a typed element. Every accessor returns `None` when
+/// the attribute is present but unusable, or given more than once, which
+/// makes the caller fall back to `Element::Other`.
+struct AttrReader<'a> {
+    attrs: &'a Attrs,
+    used: usize,
+    duplicate: bool,
+}
+
+impl<'a> AttrReader<'a> {
+    fn new(attrs: &'a Attrs) -> Self {
+        Self {
+            attrs,
+            used: 0,
+            duplicate: false,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<Option<&'a Option<String>>> {
+        let mut found = self.attrs.iter().filter(|(k, _)| k == key);
+        let value = found.next().map(|(_, v)| v);
+        if found.next().is_some() {
+            self.duplicate = true;
+            return None;
+        }
+        if value.is_some() {
+            self.used += 1;
+        }
+        Some(value)
+    }
+
+    fn string(&mut self, key: &str) -> Option<Option<String>> {
+        match self.get(key)? {
+            None => Some(None),
+            Some(Some(v)) => Some(Some(v.clone())),
+            Some(None) => None,
+        }
+    }
+
+    fn required(&mut self, key: &str) -> Option<String> {
+        self.string(key)?
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, key: &str) -> Option<Option<T>> {
+        match self.get(key)? {
+            None => Some(None),
+            Some(Some(v)) => v.parse().ok().map(Some),
+            // a bare attribute is a boolean flag set to true
+            Some(None) => "true".parse().ok().map(Some),
+        }
+    }
+
+    fn exhausted(&self) -> bool {
+        !self.duplicate && self.used == self.attrs.len()
+    }
+}
+
+pub fn escape(s: &str, in_attr: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if in_attr => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "quot" => '"',
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "apos" => '\'',
+                entity => {
+                    let code = entity.strip_prefix('#')?;
+                    let code = match code.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => code.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+impl Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Self::Text(text) = self {
+            return f.write_str(&escape(text, false));
+        }
+        let node = self.clone().into_node();
+        write!(f, "<{}", node.tag)?;
+        for (k, v) in &node.attrs {
+            match v {
+                Some(v) => write!(f, " {}=\"{}\"", k, escape(v, true))?,
+                None => write!(f, " {}", k)?,
+            }
+        }
+        if node.children.is_empty() {
+            return f.write_str("/>");
+        }
+        f.write_char('>')?;
+        for child in &node.children {
+            write!(f, "{child}")?;
+        }
+        write!(f, "</{}>", node.tag)
+    }
+}
+
+pub fn stringify(elements: &[Element]) -> String {
+    elements.iter().map(|e| e.to_string()).collect()
+}
+
+/// Parses Satori message markup.
+///
+/// Parsing is lenient the same way the reference implementation is: stray
+/// `<` are kept as text, unclosed tags are closed at the end of input and
+/// mismatched closing tags are dropped.
+pub fn parse(s: &str) -> Vec<Element> {
+    let mut stack: Vec<(Node, Vec<Element>)> = vec![];
+    let mut current = vec![];
+    let mut rest = s;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            push_text(&mut current, rest);
+            break;
+        };
+        push_text(&mut current, &rest[..start]);
+        rest = &rest[start..];
+        match parse_tag(rest) {
+            Some((Tag::Open(node, self_closing), len)) => {
+                rest = &rest[len..];
+                if self_closing {
+                    current.push(Element::from_node(node));
+                } else {
+                    stack.push((node, std::mem::take(&mut current)));
+                }
+            }
+            Some((Tag::Close(tag), len)) => {
+                rest = &rest[len..];
+                if let Some(depth) = stack.iter().rposition(|(node, _)| node.tag == tag) {
+                    while stack.len() > depth {
+                        close(&mut stack, &mut current);
+                    }
+                }
+            }
+            None => {
+                push_text(&mut current, "<");
+                rest = &rest[1..];
+            }
+        }
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut current);
+    }
+    current
+}
+
+fn close(stack: &mut Vec<(Node, Vec<Element>)>, current: &mut Vec<Element>) {
+    let (mut node, parent) = stack.pop().unwrap();
+    node.children = std::mem::replace(current, parent);
+    current.push(Element::from_node(node));
+}
+
+fn push_text(elements: &mut Vec<Element>, raw: &str) {
+    if raw.is_empty() {
+        return;
+    }
+    let text = unescape(raw);
+    if let Some(Element::Text(last)) = elements.last_mut() {
+        last.push_str(&text);
+    } else {
+        elements.push(Element::Text(text));
+    }
+}
+
+enum Tag {
+    Open(Node, bool),
+    Close(String),
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')
+}
+
+fn take_name(s: &str) -> (&str, &str) {
+    let end = s.find(|c| !is_name_char(c)).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn parse_tag(s: &str) -> Option<(Tag, usize)> {
+    let body = s.strip_prefix('<')?;
+    if let Some(body) = body.strip_prefix('/') {
+        let (name, rest) = take_name(body);
+        let rest = rest.trim_start().strip_prefix('>')?;
+        if name.is_empty() {
+            return None;
+        }
+        return Some((Tag::Close(name.to_owned()), s.len() - rest.len()));
+    }
+    let (name, mut rest) = take_name(body);
+    if name.is_empty() {
+        return None;
+    }
+    let mut attrs = Attrs::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix("/>") {
+            return Some((Tag::Open(node(name, attrs), true), s.len() - r.len()));
+        }
+        if let Some(r) = rest.strip_prefix('>') {
+            return Some((Tag::Open(node(name, attrs), false), s.len() - r.len()));
+        }
+        let (key, r) = take_name(rest);
+        if key.is_empty() {
+            return None;
+        }
+        rest = r.trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(r) => {
+                let r = r.trim_start();
+                let quote = r.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+                let r = &r[1..];
+                let end = r.find(quote)?;
+                rest = &r[end + 1..];
+                Some(unescape(&r[..end]))
+            }
+            None => None,
+        };
+        attrs.push((key.to_owned(), value));
+    }
+}
+
+fn node(tag: &str, attrs: Attrs) -> Node {
+    Node {
+        tag: tag.to_owned(),
+        attrs,
+        children: vec![],
+    }
+}
@@ -0,0 +1,210 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{Argv, Button, Channel, Event, Guild, GuildMember, GuildRole, Login, Message, User};
+
+#[derive(Clone, Debug)]
+pub enum EventKind {
+    MessageCreated(MessageEvent),
+    MessageUpdated(MessageEvent),
+    MessageDeleted(MessageEvent),
+    GuildAdded(GuildEvent),
+    GuildUpdated(GuildEvent),
+    GuildRemoved(GuildEvent),
+    GuildRequest(GuildEvent),
+    GuildMemberAdded(GuildMemberEvent),
+    GuildMemberUpdated(GuildMemberEvent),
+    GuildMemberRemoved(GuildMemberEvent),
+    GuildMemberRequest(GuildMemberEvent),
+    GuildRoleCreated(GuildRoleEvent),
+    GuildRoleUpdated(GuildRoleEvent),
+    GuildRoleDeleted(GuildRoleEvent),
+    LoginAdded(Login),
+    LoginRemoved(Login),
+    LoginUpdated(Login),
+    ReactionAdded(ReactionEvent),
+    ReactionRemoved(ReactionEvent),
+    FriendRequest(FriendRequestEvent),
+    InteractionButton(ButtonEvent),
+    InteractionCommand(CommandEvent),
+    Internal(InternalEvent),
+    /// An event of a type this crate does not know, or whose payload is
+    /// missing required fields. The original event is kept untouched.
+    Unknown(Box<Event>),
+}
+
+#[derive(Clone, Debug)]
+pub struct MessageEvent {
+    pub message: Message,
+    pub channel: Channel,
+    pub user: User,
+    pub guild: Option<Guild>,
+    pub member: Option<GuildMember>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildEvent {
+    pub guild: Guild,
+    pub user: Option<User>,
+    pub operator: Option<User>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildMemberEvent {
+    pub guild: Guild,
+    pub user: User,
+    pub member: Option<GuildMember>,
+    pub operator: Option<User>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildRoleEvent {
+    pub guild: Guild,
+    pub role: GuildRole,
+    pub operator: Option<User>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReactionEvent {
+    pub message: Message,
+    pub channel: Channel,
+    pub user: User,
+    pub guild: Option<Guild>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FriendRequestEvent {
+    pub user: User,
+}
+
+#[derive(Clone, Debug)]
+pub struct ButtonEvent {
+    pub button: Button,
+    pub channel: Option<Channel>,
+    pub guild: Option<Guild>,
+    pub user: Option<User>,
+    pub member: Option<GuildMember>,
+    pub message: Option<Message>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CommandEvent {
+    pub argv: Option<Argv>,
+    pub channel: Option<Channel>,
+    pub guild: Option<Guild>,
+    pub user: Option<User>,
+    pub member: Option<GuildMember>,
+    pub message: Option<Message>,
+}
+
+#[derive(Clone, Debug)]
+pub struct InternalEvent {
+    pub ty: String,
+    pub data: Value,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        typed(self).unwrap_or_else(|| EventKind::Unknown(Box::new(self.clone())))
+    }
+
+    fn extra<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.extra
+            .get(key)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+}
+
+impl From<Event> for EventKind {
+    fn from(event: Event) -> Self {
+        typed(&event).unwrap_or_else(|| EventKind::Unknown(Box::new(event)))
+    }
+}
+
+fn typed(e: &Event) -> Option<EventKind> {
+    let message = || -> Option<MessageEvent> {
+        Some(MessageEvent {
+            message: e.message.clone()?,
+            channel: e.channel.clone()?,
+            user: e.user.clone()?,
+            guild: e.guild.clone(),
+            member: e.member.clone(),
+        })
+    };
+    let guild = || -> Option<GuildEvent> {
+        Some(GuildEvent {
+            guild: e.guild.clone()?,
+            user: e.user.clone(),
+            operator: e.operator.clone(),
+        })
+    };
+    let member = || -> Option<GuildMemberEvent> {
+        Some(GuildMemberEvent {
+            guild: e.guild.clone()?,
+            user: e.user.clone()?,
+            member: e.member.clone(),
+            operator: e.operator.clone(),
+        })
+    };
+    let role = || -> Option<GuildRoleEvent> {
+        Some(GuildRoleEvent {
+            guild: e.guild.clone()?,
+            role: e.role.clone()?,
+            operator: e.operator.clone(),
+        })
+    };
+    let reaction = || -> Option<ReactionEvent> {
+        Some(ReactionEvent {
+            message: e.message.clone()?,
+            channel: e.channel.clone()?,
+            user: e.user.clone()?,
+            guild: e.guild.clone(),
+        })
+    };
+    let login = || e.login.clone();
+    Some(match e.ty.as_str() {
+        "message-created" => EventKind::MessageCreated(message()?),
+        "message-updated" => EventKind::MessageUpdated(message()?),
+        "message-deleted" => EventKind::MessageDeleted(message()?),
+        "guild-added" => EventKind::GuildAdded(guild()?),
+        "guild-updated" => EventKind::GuildUpdated(guild()?),
+        "guild-removed" => EventKind::GuildRemoved(guild()?),
+        "guild-request" => EventKind::GuildRequest(guild()?),
+        "guild-member-added" => EventKind::GuildMemberAdded(member()?),
+        "guild-member-updated" => EventKind::GuildMemberUpdated(member()?),
+        "guild-member-removed" => EventKind::GuildMemberRemoved(member()?),
+        "guild-member-request" => EventKind::GuildMemberRequest(member()?),
+        "guild-role-created" => EventKind::GuildRoleCreated(role()?),
+        "guild-role-updated" => EventKind::GuildRoleUpdated(role()?),
+        "guild-role-deleted" => EventKind::GuildRoleDeleted(role()?),
+        "login-added" => EventKind::LoginAdded(login()?),
+        "login-removed" => EventKind::LoginRemoved(login()?),
+        "login-updated" => EventKind::LoginUpdated(login()?),
+        "reaction-added" => EventKind::ReactionAdded(reaction()?),
+        "reaction-removed" => EventKind::ReactionRemoved(reaction()?),
+        "friend-request" => EventKind::FriendRequest(FriendRequestEvent {
+            user: e.user.clone()?,
+        }),
+        "interaction/button" => EventKind::InteractionButton(ButtonEvent {
+            button: e.button.clone()?,
+            channel: e.channel.clone(),
+            guild: e.guild.clone(),
+            user: e.user.clone(),
+            member: e.member.clone(),
+            message: e.message.clone(),
+        }),
+        "interaction/command" => EventKind::InteractionCommand(CommandEvent {
+            argv: e.argv.clone(),
+            channel: e.channel.clone(),
+            guild: e.guild.clone(),
+            user: e.user.clone(),
+            member: e.member.clone(),
+            message: e.message.clone(),
+        }),
+        "internal" => EventKind::Internal(InternalEvent {
+            ty: e.extra("_type")?,
+            data: e.extra("_data").unwrap_or(Value::Null),
+        }),
+        _ => return None,
+    })
+}
@@ -0,0 +1,112 @@
+use async_trait::async_trait;
+use std::ops::Not;
+use std::sync::Arc;
+
+use crate::{AppT, BotId, ChannelType, Event, EventMiddleware, Satori, SdkT, User};
+
+/// A predicate over events, combined with [`Filter::and`], [`Filter::or`]
+/// and `!`.
+///
+/// As a middleware it drops the events it does not match, so it can be
+/// layered on an app: `app.layer(platform("discord").and(!user_is_bot()))`.
+#[derive(Clone)]
+pub struct Filter(Arc<dyn Fn(&Event) -> bool + Send + Sync>);
+
+impl Filter {
+    pub fn new(f: impl Fn(&Event) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.0)(event)
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::new(move |e| self.matches(e) && other.matches(e))
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::new(move |e| self.matches(e) || other.matches(e))
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::new(move |e| !self.matches(e))
+    }
+}
+
+#[async_trait]
+impl EventMiddleware for Filter {
+    async fn on_event<S, A>(&self, _s: &Arc<Satori<S, A>>, event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.matches(&event).then_some(event)
+    }
+}
+
+// the user of an event, falling back to the user of its member
+fn user_of(e: &Event) -> Option<&User> {
+    e.user
+        .as_ref()
+        .or_else(|| e.member.as_ref().and_then(|m| m.user.as_ref()))
+}
+
+/// Matches every event.
+pub fn all() -> Filter {
+    Filter::new(|_| true)
+}
+
+pub fn platform(platform: impl Into<String>) -> Filter {
+    let platform = platform.into();
+    Filter::new(move |e| e.platform == platform)
+}
+
+/// Events received by the bot.
+pub fn bot(bot: &BotId) -> Filter {
+    let bot = bot.clone();
+    Filter::new(move |e| e.platform == bot.platform && e.self_id == bot.id)
+}
+
+/// Events of the given type, such as `message-created`.
+pub fn event_type(ty: impl Into<String>) -> Filter {
+    let ty = ty.into();
+    Filter::new(move |e| e.ty == ty)
+}
+
+pub fn guild(id: impl Into<String>) -> Filter {
+    let id = id.into();
+    Filter::new(move |e| e.guild.as_ref().is_some_and(|g| g.id == id))
+}
+
+pub fn channel(id: impl Into<String>) -> Filter {
+    let id = id.into();
+    Filter::new(move |e| e.channel.as_ref().is_some_and(|c| c.id == id))
+}
+
+/// Events in direct message channels.
+pub fn direct() -> Filter {
+    Filter::new(|e| {
+        e.channel
+            .as_ref()
+            .is_some_and(|c| matches!(c.ty, ChannelType::Direct))
+    })
+}
+
+pub fn user(id: impl Into<String>) -> Filter {
+    let id = id.into();
+    Filter::new(move |e| user_of(e).is_some_and(|u| u.id == id))
+}
+
+pub fn user_is_bot() -> Filter {
+    Filter::new(|e| user_of(e).is_some_and(|u| u.is_bot == Some(true)))
+}
+
+/// Events caused by the bot itself.
+pub fn user_is_self() -> Filter {
+    Filter::new(|e| user_of(e).is_some_and(|u| u.id == e.self_id))
+}
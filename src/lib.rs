@@ -1,13 +1,39 @@
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
 
+mod api;
+pub use api::{ApiCaller, Bot};
+pub mod command;
+mod dispatch;
+pub use dispatch::{DispatchConfig, DispatchFull, DispatchKey, Dispatcher};
+mod dynamic;
+pub use dynamic::{DynApp, DynConfig, DynSatori, DynSdk, Plugin};
+pub mod element;
+mod events;
+pub use events::*;
+pub mod filter;
+mod middleware;
+pub use middleware::{
+    ApiCall, ApiKind, ApiMiddleware, EventMiddleware, Layered, Next, WithMiddleware,
+};
+mod multi;
 mod net;
-pub use net::{NetAPPConfig, NetSDKConfig};
+pub use net::{
+    NetAPPConfig, NetApp, NetSDK, NetSDKConfig, NetSDKMode, ReconnectConfig, ScopedToken,
+    WebhookConfig,
+};
+mod paginate;
+pub use paginate::{paginate, paginate_bidi};
+mod session;
+pub use session::Session;
 mod structs;
 pub use structs::*;
+mod suppress;
+pub use suppress::{Suppress, SuppressCounters};
 
 pub const SATORI: &str = "Satori";
 
@@ -15,9 +41,10 @@ pub struct Satori<S, A> {
     s: S,
     a: A,
     stx: tokio::sync::broadcast::Sender<()>,
+    prompts: Arc<session::Prompts>,
 }
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct BotId {
     pub id: String,
     pub platform: String,
@@ -25,14 +52,79 @@ pub struct BotId {
 
 #[derive(Debug)]
 pub enum CallApiError {
-    BadRequest,
-    Unauthorized,
-    Forbidden,
-    NotFound,
-    MethodNotAllowed,
-    ServerError(u16),
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    MethodNotAllowed(String),
+    ServerError(u16, String),
+    UnexpectedStatus(u16, String),
 
     DeserializeFailed(serde_json::Error),
+    /// The request data could not be turned into JSON.
+    SerializeFailed(serde_json::Error),
+    Transport(hyper::Error),
+    Timeout,
+    /// The peer answered with something that does not follow the protocol.
+    Protocol(String),
+}
+
+impl CallApiError {
+    /// Maps a non-success HTTP status and its body to the matching variant.
+    pub fn from_status(status: u16, body: String) -> Self {
+        match status {
+            400 => Self::BadRequest(body),
+            401 => Self::Unauthorized(body),
+            403 => Self::Forbidden(body),
+            404 => Self::NotFound(body),
+            405 => Self::MethodNotAllowed(body),
+            500..=599 => Self::ServerError(status, body),
+            _ => Self::UnexpectedStatus(status, body),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::BadRequest(_) | Self::DeserializeFailed(_) => 400,
+            Self::Unauthorized(_) => 401,
+            Self::Forbidden(_) => 403,
+            Self::NotFound(_) => 404,
+            Self::MethodNotAllowed(_) => 405,
+            Self::ServerError(code, _) | Self::UnexpectedStatus(code, _) => *code,
+            Self::SerializeFailed(_) => 500,
+            Self::Transport(_) | Self::Protocol(_) => 502,
+            Self::Timeout => 504,
+        }
+    }
+}
+
+impl std::fmt::Display for CallApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadRequest(body) => write!(f, "bad request: {body}"),
+            Self::Unauthorized(body) => write!(f, "unauthorized: {body}"),
+            Self::Forbidden(body) => write!(f, "forbidden: {body}"),
+            Self::NotFound(body) => write!(f, "not found: {body}"),
+            Self::MethodNotAllowed(body) => write!(f, "method not allowed: {body}"),
+            Self::ServerError(code, body) => write!(f, "server error {code}: {body}"),
+            Self::UnexpectedStatus(code, body) => write!(f, "unexpected status {code}: {body}"),
+            Self::DeserializeFailed(e) => write!(f, "deserialize failed: {e}"),
+            Self::SerializeFailed(e) => write!(f, "serialize failed: {e}"),
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::Protocol(e) => write!(f, "protocol error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CallApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::DeserializeFailed(e) | Self::SerializeFailed(e) => Some(e),
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 #[async_trait]
@@ -44,6 +136,32 @@ pub trait SdkT {
         A: AppT + Send + Sync + 'static;
     async fn call_api(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError>;
     async fn get_logins(&self) -> Vec<Login>;
+    /// Stores the files and returns the url of each by field name.
+    #[allow(unused_variables)]
+    async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        Err(CallApiError::NotFound("upload not supported".to_owned()))
+    }
+    /// Streams a resource whose url starts with one of the `proxy_urls` of a login.
+    #[allow(unused_variables)]
+    async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+        Err(CallApiError::NotFound("proxy not supported".to_owned()))
+    }
+    /// Platform specific api outside the standard namespace.
+    #[allow(unused_variables)]
+    async fn call_internal(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        Err(CallApiError::NotFound(format!(
+            "internal method {method} not found"
+        )))
+    }
     #[allow(unused_variables)]
     async fn on_shutdown<S, A>(&self, s: &Arc<Satori<S, A>>)
     where
@@ -64,6 +182,16 @@ pub trait AppT {
     where
         S: SdkT + Send + Sync + 'static,
         A: AppT + Send + Sync + 'static;
+    /// Runs before the event may answer a [`Session::prompt`], `None` drops
+    /// it. Middleware layered on the app runs here, not in `handle_event`.
+    #[allow(unused_variables)]
+    async fn filter_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        Some(event)
+    }
     #[allow(unused_variables)]
     async fn on_shutdown<S, A>(&self, s: &Arc<Satori<S, A>>)
     where
@@ -83,6 +211,7 @@ where
             s,
             a,
             stx: tokio::sync::broadcast::channel(4).0,
+            prompts: Default::default(),
         })
     }
     pub async fn start_and_wait(self: &Arc<Self>, sdk_config: S::Config, app_config: A::Config) {
@@ -107,19 +236,52 @@ where
         bot: &BotId,
         data: Value,
     ) -> Result<T, CallApiError> {
-        self.s.call_api(api, bot, data).await.and_then(|s| {
-            tracing::trace!(target:SATORI, "recive api resp:{s}");
-            serde_json::from_str(&s).map_err(|e| CallApiError::DeserializeFailed(e))
-        })
+        self.s.call_api(api, bot, data).await.and_then(parse_resp)
+    }
+    pub async fn call_internal<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<T, CallApiError> {
+        self.s
+            .call_internal(method, bot, data)
+            .await
+            .and_then(parse_resp)
+    }
+    pub async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        self.s.upload(bot, files).await
+    }
+    pub async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+        self.s.fetch_resource(url).await
     }
     pub async fn handle_event(self: &Arc<Self>, event: Event) {
-        self.a.handle_event(self, event).await
+        if let Some(event) = self.filter_event(event).await {
+            self.a.handle_event(self, event).await
+        }
+    }
+    /// Runs the event middleware of the app and offers the event to prompts,
+    /// gives it back when it is left for the app to handle.
+    pub(crate) async fn filter_event(self: &Arc<Self>, event: Event) -> Option<Event> {
+        let event = self.a.filter_event(self, event).await?;
+        self.prompts.offer(event)
     }
     pub fn get_stx(&self) -> tokio::sync::broadcast::Sender<()> {
         self.stx.clone()
     }
 }
 
+pub(crate) fn parse_resp<T: DeserializeOwned>(s: String) -> Result<T, CallApiError> {
+    tracing::trace!(target:SATORI, "recive api resp:{s}");
+    // apis without a result may answer with an empty body
+    let s = if s.trim().is_empty() { "null" } else { &s };
+    serde_json::from_str(s).map_err(CallApiError::DeserializeFailed)
+}
+
 pub type SatoriApp<A> = Satori<net::NetSDK, A>;
 
 pub type SatoriSDK<S> = Satori<S, net::NetApp>;
@@ -130,6 +292,7 @@ impl<A> SatoriApp<A> {
             s: net::NetSDK::default(),
             a: app,
             stx: tokio::sync::broadcast::channel(4).0,
+            prompts: Default::default(),
         })
     }
 }
@@ -140,6 +303,7 @@ impl<S> SatoriSDK<S> {
             s: sdk,
             a: net::NetApp::new(),
             stx: tokio::sync::broadcast::channel(4).0,
+            prompts: Default::default(),
         })
     }
 }
@@ -0,0 +1,223 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+use crate::{AppT, BotId, CallApiError, Event, Login, ProxyResource, Satori, SdkT, UploadFile};
+
+/// Intercepts events before they reach an app, and before they may answer
+/// a [`Session::prompt`](crate::Session::prompt).
+#[async_trait]
+pub trait EventMiddleware {
+    /// Returns the event to pass on, possibly changed, or `None` to drop it.
+    async fn on_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static;
+}
+
+/// Intercepts api calls before they reach an sdk.
+#[async_trait]
+pub trait ApiMiddleware {
+    /// Answers the call, usually by running it through `next` once or more.
+    async fn call_api<S>(&self, call: ApiCall, next: Next<'_, S>) -> Result<String, CallApiError>
+    where
+        S: SdkT + Send + Sync;
+}
+
+#[derive(Clone, Debug)]
+pub struct ApiCall {
+    pub api: String,
+    pub bot: BotId,
+    pub data: Value,
+    pub kind: ApiKind,
+}
+
+/// Which method of the sdk an [`ApiCall`] ends up in.
+#[derive(Clone, Debug, Default)]
+pub enum ApiKind {
+    /// [`SdkT::call_api`].
+    #[default]
+    Standard,
+    /// [`SdkT::call_internal`], `api` is the method name.
+    Internal,
+    /// [`SdkT::upload`] of these files with `api` set to `upload.create`,
+    /// answered with a json object of the url of each file.
+    Upload(Vec<UploadFile>),
+}
+
+/// The rest of the pipeline behind an [`ApiMiddleware`].
+pub struct Next<'a, S> {
+    sdk: &'a S,
+}
+
+impl<S> Clone for Next<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Next<'_, S> {}
+
+impl<'a, S: SdkT + Send + Sync> Next<'a, S> {
+    pub fn new(sdk: &'a S) -> Self {
+        Self { sdk }
+    }
+
+    pub async fn run(self, call: ApiCall) -> Result<String, CallApiError> {
+        match call.kind {
+            ApiKind::Standard => self.sdk.call_api(&call.api, &call.bot, call.data).await,
+            ApiKind::Internal => {
+                self.sdk
+                    .call_internal(&call.api, &call.bot, call.data)
+                    .await
+            }
+            ApiKind::Upload(files) => {
+                let urls = self.sdk.upload(&call.bot, files).await?;
+                serde_json::to_string(&urls).map_err(CallApiError::SerializeFailed)
+            }
+        }
+    }
+}
+
+/// An app or sdk wrapped by a middleware. It is an [`AppT`] when the
+/// middleware is an [`EventMiddleware`] and an [`SdkT`] when it is an
+/// [`ApiMiddleware`], everything else is passed to the inner one untouched.
+pub struct Layered<T, M> {
+    pub inner: T,
+    pub middleware: M,
+}
+
+pub trait WithMiddleware: Sized {
+    /// Wraps `self` in `middleware`. The last layer added is the outermost,
+    /// it sees events and calls first.
+    fn layer<M>(self, middleware: M) -> Layered<Self, M> {
+        Layered {
+            inner: self,
+            middleware,
+        }
+    }
+}
+
+impl<T> WithMiddleware for T {}
+
+#[async_trait]
+impl<T, M> AppT for Layered<T, M>
+where
+    T: AppT + Send + Sync,
+    T::Config: Send,
+    M: EventMiddleware + Send + Sync,
+{
+    type Config = T::Config;
+
+    async fn start<S, A>(&self, s: &Arc<Satori<S, A>>, config: Self::Config) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.start(s, config).await
+    }
+
+    async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.handle_event(s, event).await
+    }
+
+    async fn filter_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let event = self.middleware.on_event(s, event).await?;
+        self.inner.filter_event(s, event).await
+    }
+
+    async fn on_shutdown<S, A>(&self, s: &Arc<Satori<S, A>>)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.on_shutdown(s).await
+    }
+}
+
+#[async_trait]
+impl<T, M> SdkT for Layered<T, M>
+where
+    T: SdkT + Send + Sync,
+    T::Config: Send,
+    M: ApiMiddleware + Send + Sync,
+{
+    type Config = T::Config;
+
+    async fn start<S, A>(&self, s: &Arc<Satori<S, A>>, config: Self::Config) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.start(s, config).await
+    }
+
+    async fn call_api(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError> {
+        let call = ApiCall {
+            api: api.to_owned(),
+            bot: bot.clone(),
+            data,
+            kind: ApiKind::Standard,
+        };
+        self.middleware.call_api(call, Next::new(&self.inner)).await
+    }
+
+    async fn get_logins(&self) -> Vec<Login> {
+        self.inner.get_logins().await
+    }
+
+    async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        let call = ApiCall {
+            api: "upload.create".to_owned(),
+            bot: bot.clone(),
+            data: Value::Null,
+            kind: ApiKind::Upload(files),
+        };
+        let urls = self
+            .middleware
+            .call_api(call, Next::new(&self.inner))
+            .await?;
+        serde_json::from_str(&urls).map_err(CallApiError::DeserializeFailed)
+    }
+
+    async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+        self.inner.fetch_resource(url).await
+    }
+
+    async fn call_internal(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        let call = ApiCall {
+            api: method.to_owned(),
+            bot: bot.clone(),
+            data,
+            kind: ApiKind::Internal,
+        };
+        self.middleware.call_api(call, Next::new(&self.inner)).await
+    }
+
+    async fn on_shutdown<S, A>(&self, s: &Arc<Satori<S, A>>)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.inner.on_shutdown(s).await
+    }
+}
@@ -0,0 +1,167 @@
+// Tuples of sdks and apps, so one `Satori` can host several of each.
+//
+// A tuple of sdks routes every call to the member owning the bot's login,
+// a tuple of apps gets every event delivered to each member. Tuples nest, so
+// `((NetSDK, Local), Other)` works as well.
+
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+use crate::{AppT, BotId, CallApiError, Event, Login, ProxyResource, Satori, SdkT, UploadFile};
+
+pub(crate) fn owns(logins: &[Login], bot: &BotId) -> bool {
+    logins.iter().any(|l| {
+        l.platform.as_deref() == Some(bot.platform.as_str())
+            && l.self_id.as_deref() == Some(bot.id.as_str())
+    })
+}
+
+pub(crate) fn proxies(logins: &[Login], url: &str) -> bool {
+    logins
+        .iter()
+        .any(|l| l.proxy_urls.iter().any(|p| url.starts_with(p.as_str())))
+}
+
+pub(crate) fn bot_not_found(bot: &BotId) -> CallApiError {
+    CallApiError::NotFound(format!("bot {bot:?} not found"))
+}
+
+pub(crate) fn not_proxied(url: &str) -> CallApiError {
+    CallApiError::Forbidden(format!("{url} is not a proxy url"))
+}
+
+macro_rules! tuple_impl {
+    ($($T:ident $i:tt),+) => {
+        #[async_trait]
+        impl<$($T),+> SdkT for ($($T,)+)
+        where
+            $($T: SdkT + Send + Sync, $T::Config: Send,)+
+        {
+            type Config = ($($T::Config,)+);
+
+            async fn start<S, A>(
+                &self,
+                s: &Arc<Satori<S, A>>,
+                config: Self::Config,
+            ) -> Vec<JoinHandle<()>>
+            where
+                S: SdkT + Send + Sync + 'static,
+                A: AppT + Send + Sync + 'static,
+            {
+                let mut joins = vec![];
+                $(joins.extend(self.$i.start(s, config.$i).await);)+
+                joins
+            }
+
+            async fn call_api(
+                &self,
+                api: &str,
+                bot: &BotId,
+                data: Value,
+            ) -> Result<String, CallApiError> {
+                $(if owns(&self.$i.get_logins().await, bot) {
+                    return self.$i.call_api(api, bot, data).await;
+                })+
+                Err(bot_not_found(bot))
+            }
+
+            async fn get_logins(&self) -> Vec<Login> {
+                let mut logins = vec![];
+                $(logins.extend(self.$i.get_logins().await);)+
+                logins
+            }
+
+            async fn upload(
+                &self,
+                bot: &BotId,
+                files: Vec<UploadFile>,
+            ) -> Result<HashMap<String, String>, CallApiError> {
+                $(if owns(&self.$i.get_logins().await, bot) {
+                    return self.$i.upload(bot, files).await;
+                })+
+                Err(bot_not_found(bot))
+            }
+
+            async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+                $(if proxies(&self.$i.get_logins().await, url) {
+                    return self.$i.fetch_resource(url).await;
+                })+
+                Err(not_proxied(url))
+            }
+
+            async fn call_internal(
+                &self,
+                method: &str,
+                bot: &BotId,
+                data: Value,
+            ) -> Result<String, CallApiError> {
+                $(if owns(&self.$i.get_logins().await, bot) {
+                    return self.$i.call_internal(method, bot, data).await;
+                })+
+                Err(bot_not_found(bot))
+            }
+
+            async fn on_shutdown<S, A>(&self, s: &Arc<Satori<S, A>>)
+            where
+                S: SdkT + Send + Sync + 'static,
+                A: AppT + Send + Sync + 'static,
+            {
+                $(self.$i.on_shutdown(s).await;)+
+            }
+        }
+
+        #[async_trait]
+        impl<$($T),+> AppT for ($($T,)+)
+        where
+            $($T: AppT + Send + Sync, $T::Config: Send,)+
+        {
+            type Config = ($($T::Config,)+);
+
+            async fn start<S, A>(
+                &self,
+                s: &Arc<Satori<S, A>>,
+                config: Self::Config,
+            ) -> Vec<JoinHandle<()>>
+            where
+                S: SdkT + Send + Sync + 'static,
+                A: AppT + Send + Sync + 'static,
+            {
+                let mut joins = vec![];
+                $(joins.extend(self.$i.start(s, config.$i).await);)+
+                joins
+            }
+
+            async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
+            where
+                S: SdkT + Send + Sync + 'static,
+                A: AppT + Send + Sync + 'static,
+            {
+                // every app sees the event at once, a slow one does not hold
+                // back the others. Their own middleware runs after prompts
+                // were offered the event.
+                tokio::join!($(async {
+                    if let Some(event) = self.$i.filter_event(s, event.clone()).await {
+                        self.$i.handle_event(s, event).await
+                    }
+                }),+);
+            }
+
+            async fn on_shutdown<S, A>(&self, s: &Arc<Satori<S, A>>)
+            where
+                S: SdkT + Send + Sync + 'static,
+                A: AppT + Send + Sync + 'static,
+            {
+                $(self.$i.on_shutdown(s).await;)+
+            }
+        }
+    };
+}
+
+tuple_impl!(T0 0, T1 1);
+tuple_impl!(T0 0, T1 1, T2 2);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4);
+tuple_impl!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5);
@@ -1,26 +1,86 @@
-use super::Signal;
-use crate::{AppT, BotId, Event, Satori, SdkT, SATORI};
+use super::{token_eq, Identify, Meta, Signal, WebhookConfig, Webhooks};
+use crate::{AppT, BotId, CallApiError, Event, Login, Satori, SdkT, UploadFile, SATORI};
 
 use async_trait::async_trait;
-use axum::extract::{Path, WebSocketUpgrade};
-use axum::response::IntoResponse;
+use axum::extract::multipart::{Multipart, MultipartError};
+use axum::extract::ws::CloseFrame;
+use axum::extract::{DefaultBodyLimit, Path, WebSocketUpgrade};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use futures_util::StreamExt;
-use hyper::{HeaderMap, StatusCode};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{HeaderMap, StatusCode, Uri};
+use serde::Deserialize;
 use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 pub struct NetApp {
     tx: tokio::sync::broadcast::Sender<Event>,
+    replay: Arc<Mutex<ReplayBuffer>>,
+    webhooks: Arc<Webhooks>,
+}
+
+/// Recent events kept for clients that reconnect with an IDENTIFY sequence.
+struct ReplayBuffer {
+    events: VecDeque<Event>,
+    capacity: usize,
+    // the `sn` of the last event, events are numbered here rather than
+    // trusting the sdk to number them in order
+    sn: i64,
+}
+
+impl ReplayBuffer {
+    /// Numbers `event` and keeps it.
+    fn push(&mut self, mut event: Event) -> Event {
+        self.sn += 1;
+        event.sn = self.sn;
+        if self.capacity == 0 {
+            return event;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+        event
+    }
+
+    fn since(&self, sequence: i64) -> Vec<Event> {
+        self.events
+            .iter()
+            .filter(|e| e.sn > sequence)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for NetApp {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NetApp {
     pub fn new() -> Self {
+        Self::with_replay_capacity(1024)
+    }
+
+    /// `capacity` is the number of recent events kept for replay, 0 disables replay.
+    pub fn with_replay_capacity(capacity: usize) -> Self {
         let (tx, _) = tokio::sync::broadcast::channel(128);
-        Self { tx }
+        Self {
+            tx,
+            replay: Arc::new(Mutex::new(ReplayBuffer {
+                events: VecDeque::with_capacity(capacity),
+                capacity,
+                sn: 0,
+            })),
+            webhooks: Default::default(),
+        }
     }
 }
 
@@ -28,7 +88,56 @@ impl NetApp {
 pub struct NetAPPConfig {
     pub host: IpAddr,
     pub port: u16,
+    /// Token granting access to every bot.
     pub authorize: Option<String>,
+    /// Additional tokens restricted to a set of bots.
+    pub tokens: Vec<ScopedToken>,
+    /// Targets every event is POSTed to, alongside the WebSocket clients.
+    pub webhooks: Vec<WebhookConfig>,
+    /// Largest accepted `upload.create` body in bytes.
+    pub upload_limit: usize,
+}
+
+#[derive(Clone)]
+pub struct ScopedToken {
+    pub token: String,
+    pub bots: Vec<BotId>,
+}
+
+enum Scope<'a> {
+    All,
+    Bots(&'a [BotId]),
+}
+
+impl Scope<'_> {
+    fn allows(&self, platform: &str, id: &str) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Bots(bots) => bots.iter().any(|b| b.platform == platform && b.id == id),
+        }
+    }
+}
+
+impl NetAPPConfig {
+    /// Returns what `token` may access, `None` if it is rejected. Without any
+    /// token configured the server is open.
+    fn scope(&self, token: Option<&str>) -> Option<Scope<'_>> {
+        if self.authorize.is_none() && self.tokens.is_empty() {
+            return Some(Scope::All);
+        }
+        let token = token?;
+        if self
+            .authorize
+            .as_deref()
+            .is_some_and(|a| token_eq(a, token))
+        {
+            return Some(Scope::All);
+        }
+        self.tokens
+            .iter()
+            .find(|t| token_eq(&t.token, token))
+            .map(|t| Scope::Bots(&t.bots))
+    }
 }
 
 #[async_trait]
@@ -41,9 +150,17 @@ impl AppT for NetApp {
     {
         let mut joins = vec![];
         for net in config {
+            for webhook in &net.webhooks {
+                if let Err(e) = self.webhooks.add(webhook.clone(), &s.get_stx()).await {
+                    error!(target: SATORI, "invalid webhook url {}: {e}", webhook.url);
+                }
+            }
             let tx = self.tx.clone();
+            let replay = self.replay.clone();
+            let webhooks = self.webhooks.clone();
             let stx = s.get_stx();
             let s = s.clone();
+            let net = Arc::new(net);
             joins.push(tokio::spawn(async move {
                 let mut srx = stx.subscribe();
                 let app = axum::Router::new()
@@ -51,21 +168,67 @@ impl AppT for NetApp {
                         "/v1/events",
                         axum::routing::get({
                             let s = s.clone();
-                            move |ws| ws_handle(ws, tx, stx, s)
+                            let net = net.clone();
+                            move |ws| ws_handle(ws, tx, replay, stx, net, s)
+                        }),
+                    )
+                    .route(
+                        "/v1/meta",
+                        axum::routing::post({
+                            let s = s.clone();
+                            let net = net.clone();
+                            let webhooks = webhooks.clone();
+                            move |map| meta_handle(map, net, webhooks, s)
+                        }),
+                    )
+                    .route(
+                        "/v1/meta/:action",
+                        axum::routing::post({
+                            let s = s.clone();
+                            let net = net.clone();
+                            let webhooks = webhooks.clone();
+                            move |path, map, data| {
+                                meta_webhook_handle(path, map, data, net, webhooks, s)
+                            }
+                        }),
+                    )
+                    .route(
+                        "/v1/proxy/*url",
+                        axum::routing::get({
+                            let s = s.clone();
+                            move |uri| proxy_handle(uri, s)
+                        }),
+                    )
+                    .route(
+                        "/v1/upload.create",
+                        axum::routing::post({
+                            let s = s.clone();
+                            let net = net.clone();
+                            move |map, form| upload_handle(map, form, net, s)
+                        })
+                        .layer(DefaultBodyLimit::max(net.upload_limit)),
+                    )
+                    .route(
+                        "/v1/internal/:method",
+                        axum::routing::post({
+                            let s = s.clone();
+                            let net = net.clone();
+                            move |path, map, data| internal_handle(path, map, data, net, s)
                         }),
                     )
                     .route(
                         "/v1/:api",
-                        axum::routing::post(move |path, map, data| api_handle(path, map, data, s)),
+                        axum::routing::post({
+                            let net = net.clone();
+                            move |path, map, data| api_handle(path, map, data, net, s)
+                        }),
                     );
                 let server = axum::Server::bind(&(net.host, net.port).into())
                     .serve(app.into_make_service_with_connect_info::<SocketAddr>());
                 info!(target: SATORI, "Start server in {}:{}", net.host, net.port);
-                loop {
-                    tokio::select! {
-                        _ = server => return,
-                        Ok(_) = srx.recv() => return,
-                    }
+                tokio::select! {
+                    _ = server => {}
+                    _ = srx.recv() => {}
                 }
             }));
         }
@@ -76,6 +239,10 @@ impl AppT for NetApp {
         S: SdkT + Send + Sync + 'static,
         A: AppT + Send + Sync + 'static,
     {
+        // hold the lock while broadcasting so replay and live streams stay in order
+        let mut replay = self.replay.lock().await;
+        let event = replay.push(event);
+        self.webhooks.send(&event).await;
         self.tx.send(event).ok();
     }
 }
@@ -83,7 +250,9 @@ impl AppT for NetApp {
 async fn ws_handle<S, A>(
     ws: WebSocketUpgrade,
     tx: tokio::sync::broadcast::Sender<Event>,
+    replay: Arc<Mutex<ReplayBuffer>>,
     stx: tokio::sync::broadcast::Sender<()>,
+    net: Arc<NetAPPConfig>,
     s: Arc<Satori<S, A>>,
 ) -> impl IntoResponse
 where
@@ -94,18 +263,38 @@ where
     let mut srx = stx.subscribe();
     ws.on_upgrade(move |mut socket| async move {
         info!(target: SATORI, "new WebSocket client acceptted.");
+        // live events are held back until IDENTIFY, anything up to `last_id`
+        // was in the buffer at IDENTIFY, so it was replayed or not asked for
+        let mut scope = None;
+        let mut last_id = None;
         loop {
             tokio::select! {
-                Ok(event) = rx.recv() => {
-                    if let Err(e) = socket
-                        .send(serde_json::to_string(&Signal::event(event)).unwrap().into())
-                        .await
-                    {
+                Ok(event) = rx.recv(), if scope.is_some() => {
+                    if last_id.is_some_and(|id| event.sn <= id) {
+                        continue;
+                    }
+                    if !scope.as_ref().is_some_and(|s: &Scope| s.allows(&event.platform, &event.self_id)) {
+                        continue;
+                    }
+                    let login_changed = event.ty.starts_with("login-");
+                    if let Err(e) = socket.send(Signal::event(event).to_string().into()).await {
                         error!(target: SATORI, "Send event error: {e}");
                         return;
                     }
+                    if login_changed {
+                        let Some(scope) = &scope else { continue };
+                        let meta = Meta::new(logins_in_scope(&s, scope).await);
+                        if let Err(e) = socket.send(Signal::meta(meta).to_string().into()).await {
+                            error!(target: SATORI, "Send meta error: {e}");
+                            return;
+                        }
+                    }
                 }
-                Some(Ok(msg)) = socket.next() => {
+                msg = socket.next() => {
+                    let Some(Ok(msg)) = msg else {
+                        info!(target: SATORI, "WebSocket client disconnected.");
+                        return;
+                    };
                     match msg {
                         axum::extract::ws::Message::Close(_) => return,
                         axum::extract::ws::Message::Ping(b) => {
@@ -113,18 +302,56 @@ where
                         }
                         axum::extract::ws::Message::Text(text) => match serde_json::from_str(&text) {
                             Ok(Signal::<Value> { op, body }) => match op {
-                                1 => socket
-                                    .send(Signal::pong().to_string().into())
-                                    .await
-                                    .unwrap(), //todo
+                                1 => {
+                                    if let Err(e) = socket.send(Signal::pong().to_string().into()).await {
+                                        error!(target: SATORI, "Send pong error: {e}");
+                                        return;
+                                    }
+                                }
                                 3 => {
-                                    let _body = body;
-                                    socket
-                                        .send(Signal::ready(s.s.get_logins().await).to_string().into())
-                                        .await
-                                        .unwrap();
+                                    let identify = serde_json::from_value::<Identify>(body).ok();
+                                    let token = identify.as_ref().map(|i| i.token.as_str()).filter(|t| !t.is_empty());
+                                    let Some(allowed) = net.scope(token) else {
+                                        info!(target: SATORI, "WebSocket client identify with invalid token.");
+                                        socket
+                                            .send(axum::extract::ws::Message::Close(Some(CloseFrame {
+                                                code: 3000,
+                                                reason: "invalid token".into(),
+                                            })))
+                                            .await
+                                            .ok();
+                                        return;
+                                    };
+                                    let logins = logins_in_scope(&s, &allowed).await;
+                                    if let Err(e) = socket.send(Signal::ready(logins).to_string().into()).await {
+                                        error!(target: SATORI, "Send ready error: {e}");
+                                        return;
+                                    }
+                                    if let Some(sequence) = identify.and_then(|i| i.sequence) {
+                                        // the client may be ahead after a restart, so the cutoff
+                                        // is the newest event here rather than its sequence
+                                        let (newest, events) = {
+                                            let replay = replay.lock().await;
+                                            (replay.sn, replay.since(sequence))
+                                        };
+                                        last_id = Some(newest);
+                                        let events = events
+                                            .into_iter()
+                                            .filter(|e| allowed.allows(&e.platform, &e.self_id))
+                                            .collect::<Vec<_>>();
+                                        info!(target: SATORI, "replay {} events after {sequence}", events.len());
+                                        for event in events {
+                                            if let Err(e) = socket.send(Signal::event(event).to_string().into()).await {
+                                                error!(target: SATORI, "Send event error: {e}");
+                                                return;
+                                            }
+                                        }
+                                    }
+                                    scope = Some(allowed);
+                                }
+                                op => {
+                                    warn!(target: SATORI, "Protocol error: unexpected signal op {op} from client")
                                 }
-                                _ => unreachable!(),
                             },
                             Err(e) => {
                                 error!(target: SATORI, "Receive signal error: {e}")
@@ -139,16 +366,99 @@ where
     })
 }
 
-async fn api_handle<S, A>(
-    Path(api): Path<String>,
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+async fn logins_in_scope<S, A>(s: &Arc<Satori<S, A>>, scope: &Scope<'_>) -> Vec<Login>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    s.s.get_logins()
+        .await
+        .into_iter()
+        .filter(|l| {
+            scope.allows(
+                l.platform.as_deref().unwrap_or_default(),
+                l.self_id.as_deref().unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+async fn meta_handle<S, A>(
     headers: HeaderMap,
-    Json(data): Json<Value>,
+    net: Arc<NetAPPConfig>,
+    webhooks: Arc<Webhooks>,
     s: Arc<Satori<S, A>>,
-) -> Result<String, (StatusCode, String)>
+) -> Result<Json<Meta>, (StatusCode, String)>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let Some(scope) = net.scope(bearer(&headers)) else {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_owned()));
+    };
+    let mut meta = Meta::new(logins_in_scope(&s, &scope).await);
+    if let Scope::All = scope {
+        meta.webhooks = webhooks.urls().await;
+    }
+    Ok(Json(meta))
+}
+
+#[derive(Deserialize)]
+struct WebhookRequest {
+    url: String,
+    token: Option<String>,
+}
+
+async fn meta_webhook_handle<S, A>(
+    Path(action): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<WebhookRequest>,
+    net: Arc<NetAPPConfig>,
+    webhooks: Arc<Webhooks>,
+    s: Arc<Satori<S, A>>,
+) -> Result<(), (StatusCode, String)>
 where
     S: SdkT + Send + Sync + 'static,
     A: AppT + Send + Sync + 'static,
 {
+    match net.scope(bearer(&headers)) {
+        Some(Scope::All) => {}
+        Some(Scope::Bots(_)) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Token is not allowed to manage webhooks".to_owned(),
+            ))
+        }
+        None => return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_owned())),
+    }
+    match action.as_str() {
+        "webhook.create" => {
+            let mut config = WebhookConfig::new(req.url);
+            config.token = req.token;
+            webhooks
+                .add(config, &s.get_stx())
+                .await
+                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid url: {e}")))
+        }
+        "webhook.delete" => {
+            if webhooks.remove(&req.url).await {
+                Ok(())
+            } else {
+                Err((StatusCode::NOT_FOUND, "Webhook not found".to_owned()))
+            }
+        }
+        _ => Err((StatusCode::NOT_FOUND, format!("Unknown meta api {action}"))),
+    }
+}
+
+fn bot_id(headers: &HeaderMap, scope: &Scope) -> Result<BotId, (StatusCode, String)> {
     let Some(id) = headers
         .get("X-Self-ID")
         .and_then(|v| v.to_str().ok())
@@ -169,8 +479,123 @@ where
             "Platform missed or error".to_owned(),
         ));
     };
-    match s.call_api(&api, &BotId { platform, id }, data).await {
-        Ok(s) => Ok(s),
-        Err(e) => Err(e.into_resp()),
+    if !scope.allows(&platform, &id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Token is not allowed for this bot".to_owned(),
+        ));
+    }
+    Ok(BotId { platform, id })
+}
+
+async fn api_handle<S, A>(
+    Path(api): Path<String>,
+    headers: HeaderMap,
+    Json(data): Json<Value>,
+    net: Arc<NetAPPConfig>,
+    s: Arc<Satori<S, A>>,
+) -> Result<String, (StatusCode, String)>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let Some(scope) = net.scope(bearer(&headers)) else {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_owned()));
+    };
+    let bot = bot_id(&headers, &scope)?;
+    s.s.call_api(&api, &bot, data)
+        .await
+        .map_err(CallApiError::into_resp)
+}
+
+async fn upload_handle<S, A>(
+    headers: HeaderMap,
+    mut form: Multipart,
+    net: Arc<NetAPPConfig>,
+    s: Arc<Satori<S, A>>,
+) -> Result<Json<HashMap<String, String>>, (StatusCode, String)>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let Some(scope) = net.scope(bearer(&headers)) else {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_owned()));
+    };
+    let bot = bot_id(&headers, &scope)?;
+    // the status tells a body over `upload_limit` apart from a malformed one
+    let bad_form = |e: MultipartError| (e.status(), format!("Invalid form: {e}"));
+    let mut files = vec![];
+    while let Some(field) = form.next_field().await.map_err(bad_form)? {
+        let Some(name) = field.name().map(|n| n.to_owned()) else {
+            continue;
+        };
+        files.push(UploadFile {
+            name,
+            filename: field.file_name().map(|n| n.to_owned()),
+            content_type: field.content_type().map(|c| c.to_owned()),
+            data: field.bytes().await.map_err(bad_form)?.to_vec(),
+        });
     }
+    s.s.upload(&bot, files)
+        .await
+        .map(Json)
+        .map_err(CallApiError::into_resp)
+}
+
+/// Serves resources behind the `proxy_urls` advertised by the logins. Like the
+/// reference server it needs no token, so media can be embedded directly; only
+/// advertised prefixes are reachable.
+async fn proxy_handle<S, A>(
+    uri: Uri,
+    s: Arc<Satori<S, A>>,
+) -> Result<Response, (StatusCode, String)>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let path_and_query = uri.path_and_query().map(|p| p.as_str()).unwrap_or_default();
+    let Some(url) = path_and_query.strip_prefix("/v1/proxy/") else {
+        return Err((StatusCode::BAD_REQUEST, "Invalid proxy url".to_owned()));
+    };
+    let allowed =
+        s.s.get_logins()
+            .await
+            .iter()
+            .flat_map(|l| &l.proxy_urls)
+            .any(|prefix| url.starts_with(prefix.as_str()));
+    if !allowed {
+        return Err((StatusCode::FORBIDDEN, format!("{url} is not a proxy url")));
+    }
+    let resource =
+        s.s.fetch_resource(url)
+            .await
+            .map_err(CallApiError::into_resp)?;
+    let mut resp = Response::new(axum::body::boxed(resource.body));
+    if let Some(content_type) = resource
+        .content_type
+        .and_then(|c| HeaderValue::from_str(&c).ok())
+    {
+        resp.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    Ok(resp)
+}
+
+async fn internal_handle<S, A>(
+    Path(method): Path<String>,
+    headers: HeaderMap,
+    Json(data): Json<Value>,
+    net: Arc<NetAPPConfig>,
+    s: Arc<Satori<S, A>>,
+) -> Result<String, (StatusCode, String)>
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let Some(scope) = net.scope(bearer(&headers)) else {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_owned()));
+    };
+    let bot = bot_id(&headers, &scope)?;
+    s.s.call_internal(&method, &bot, data)
+        .await
+        .map_err(CallApiError::into_resp)
 }
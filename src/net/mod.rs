@@ -2,11 +2,58 @@ use crate::{CallApiError, Event, Login};
 
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 mod sdk;
 pub use sdk::*;
 mod app;
 pub use app::*;
+mod webhook;
+pub use webhook::*;
+
+/// Jittered exponential backoff used for reconnecting and retrying deliveries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconnectConfig {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Fraction of the delay randomly added or removed, between 0 and 1.
+    pub jitter: f64,
+    /// Give up after this many consecutive failed attempts, `None` retries forever.
+    pub max_retries: Option<u32>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_retries: None,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    fn delay(&self, attempt: u32) -> Duration {
+        let base = self.initial_delay.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        let base = base.min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0) * (fastrand::f64() * 2.0 - 1.0);
+        Duration::from_secs_f64((base * (1.0 + jitter)).max(0.0))
+    }
+
+    fn exhausted(&self, attempt: u32) -> bool {
+        self.max_retries.is_some_and(|max| attempt >= max)
+    }
+}
+
+// compares in time independent of where the tokens differ
+fn token_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let diff = a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y));
+    a.len() == b.len() && std::hint::black_box(diff) == 0
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Signal<T> {
@@ -31,12 +78,14 @@ impl Signal<()> {
 
 #[derive(Serialize, Deserialize)]
 pub struct Identify {
+    #[serde(default)]
     pub token: String,
-    pub sequence: i64,
+    #[serde(default)]
+    pub sequence: Option<i64>,
 }
 
 impl Signal<Identify> {
-    fn identfy(token: &str, seq: i64) -> Self {
+    fn identfy(token: &str, seq: Option<i64>) -> Self {
         Self {
             op: 3,
             body: Identify {
@@ -61,34 +110,59 @@ impl Signal<Logins> {
     }
 }
 
-impl ToString for Signal<()> {
-    fn to_string(&self) -> String {
-        serde_json::to_string(self).unwrap()
+#[derive(Serialize, Deserialize)]
+pub struct Meta {
+    pub logins: Vec<Login>,
+    #[serde(default)]
+    pub proxy_urls: Vec<String>,
+    /// Registered webhook urls, only reported to fully authorized clients.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<String>,
+}
+
+impl Meta {
+    fn new(logins: Vec<Login>) -> Self {
+        let mut proxy_urls: Vec<String> = vec![];
+        for url in logins.iter().flat_map(|l| &l.proxy_urls) {
+            if !proxy_urls.contains(url) {
+                proxy_urls.push(url.clone());
+            }
+        }
+        Self {
+            logins,
+            proxy_urls,
+            webhooks: vec![],
+        }
     }
 }
 
-impl ToString for Signal<Identify> {
-    fn to_string(&self) -> String {
-        serde_json::to_string(self).unwrap()
+impl Signal<Meta> {
+    fn meta(meta: Meta) -> Self {
+        Self { op: 5, body: meta }
     }
 }
 
-impl ToString for Signal<Logins> {
-    fn to_string(&self) -> String {
-        serde_json::to_string(self).unwrap()
+impl<T: Serialize> std::fmt::Display for Signal<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(self).unwrap())
     }
 }
 
 impl CallApiError {
     pub fn into_resp(self) -> (StatusCode, String) {
-        match self {
-            Self::BadRequest => (StatusCode::BAD_REQUEST, "".to_owned()),
-            Self::Unauthorized => (StatusCode::UNAUTHORIZED, "".to_owned()),
-            Self::Forbidden => (StatusCode::FORBIDDEN, "".to_owned()),
-            Self::NotFound => (StatusCode::NOT_FOUND, "".to_owned()),
-            Self::MethodNotAllowed => (StatusCode::METHOD_NOT_ALLOWED, "".to_owned()),
-            Self::ServerError(code) => (StatusCode::from_u16(code).unwrap(), "".to_owned()),
-            Self::DeserializeFailed(e) => (StatusCode::BAD_REQUEST, format!("{e}")),
-        }
+        let status =
+            StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = match self {
+            Self::BadRequest(body)
+            | Self::Unauthorized(body)
+            | Self::Forbidden(body)
+            | Self::NotFound(body)
+            | Self::MethodNotAllowed(body)
+            | Self::ServerError(_, body)
+            | Self::UnexpectedStatus(_, body)
+            | Self::Protocol(body) => body,
+            e => e.to_string(),
+        };
+        (status, body)
     }
 }
@@ -1,10 +1,15 @@
-use super::{Logins, Signal};
-use crate::{AppT, BotId, CallApiError, Event, Login, Satori, SdkT, SATORI};
+use super::{token_eq, Logins, ReconnectConfig, Signal};
+use crate::{
+    AppT, BotId, CallApiError, DispatchConfig, Dispatcher, Event, Login, ProxyResource, Satori,
+    SdkT, Status, UploadFile, SATORI,
+};
 
 use async_trait::async_trait;
+use axum::Json;
 use futures_util::{SinkExt, StreamExt};
-use hyper::{Body, Client, StatusCode};
+use hyper::{Body, Client, HeaderMap, StatusCode};
 use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::{collections::HashMap, time::Duration};
 use tokio::net::TcpStream;
@@ -16,24 +21,92 @@ use tokio_tungstenite::{
 };
 use tracing::{error, info, trace, warn};
 
+pub type Bots = Arc<RwLock<HashMap<BotId, (NetSDKConfig, Login)>>>;
+
 #[derive(Default)]
 pub struct NetSDK {
-    pub bots: Arc<RwLock<HashMap<BotId, NetSDKConfig>>>,
+    pub bots: Bots,
+    dispatcher: Dispatcher,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct NetSDKConfig {
-    pub host: std::net::IpAddr,
+    pub host: IpAddr,
     pub port: u16,
     pub authorize: Option<String>,
+    pub reconnect: ReconnectConfig,
+    pub api_timeout: Duration,
+    pub mode: NetSDKMode,
+}
+
+/// How events are received from the server, API calls always go to `host:port`.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum NetSDKMode {
+    #[default]
+    WebSocket,
+    /// Listen on `host:port` for events the server POSTs to `path`, with
+    /// `authorize` as the expected bearer token.
+    Webhook {
+        host: IpAddr,
+        port: u16,
+        path: String,
+    },
+}
+
+enum SessionEnd {
+    Shutdown,
+    Lost,
+}
+
+type WsStream = tokio_tungstenite::WebSocketStream<TcpStream>;
+
+async fn connect(net: &NetSDKConfig) -> Result<WsStream, tokio_tungstenite::tungstenite::Error> {
+    let uri = format!("{}:{}", net.host, net.port);
+    let stream = TcpStream::connect(&uri).await?;
+    let (ws_stream, _) = client_async(
+        Builder::new()
+            .method("GET")
+            .header("Host", net.host.to_string())
+            .header("Connection", "Upgrade")
+            .header("Upgrade", "websocket")
+            .header("Sec-WebSocket-Version", "13")
+            .header("Sec-WebSocket-Key", generate_key())
+            .uri(format!("ws://{uri}/v1/events"))
+            .body(())?,
+        stream,
+    )
+    .await?;
+    Ok(ws_stream)
+}
+
+async fn set_status(bots: &Bots, net: &NetSDKConfig, status: Status) {
+    for (bot_net, login) in bots.write().await.values_mut() {
+        if bot_net == net {
+            login.status = status.clone();
+        }
+    }
+}
+
+/// Replaces the bots of `net` with `logins`.
+async fn set_logins(bots: &Bots, net: &NetSDKConfig, logins: Vec<Login>) {
+    let mut bots = bots.write().await;
+    bots.retain(|_, (bot_net, _)| bot_net != net);
+    for login in logins {
+        let (Some(platform), Some(id)) = (login.platform.clone(), login.self_id.clone()) else {
+            warn!(target: SATORI, "Protocol error: login without platform or self_id: {login:?}");
+            continue;
+        };
+        bots.insert(BotId { platform, id }, (net.clone(), login));
+    }
 }
 
 async fn handle_signal<S, A>(
     s: &Arc<Satori<S, A>>,
     signal: Signal<Option<Value>>,
-    bots: &Arc<RwLock<HashMap<BotId, NetSDKConfig>>>,
+    bots: &Bots,
+    dispatcher: &Dispatcher,
     net: &NetSDKConfig,
-    seq: &mut i64,
+    seq: &mut Option<i64>,
 ) where
     S: SdkT + Send + Sync + 'static,
     A: AppT + Send + Sync + 'static,
@@ -43,10 +116,10 @@ async fn handle_signal<S, A>(
             if let Some(body) = signal.body {
                 match serde_json::from_value::<Event>(body) {
                     Ok(event) => {
+                        *seq = Some(event.sn);
                         info!(target: SATORI, "receive event: {:?}", event);
-                        let s = s.clone();
-                        *seq = event.id;
-                        tokio::spawn(async move { s.handle_event(event).await });
+                        // holds the read loop while the dispatcher is full
+                        dispatcher.dispatch(s, event).await;
                     }
                     Err(e) => {
                         warn!(target: SATORI, "deserlize event error:{e}");
@@ -55,28 +128,311 @@ async fn handle_signal<S, A>(
             }
         }
         2 => {}
-        4 => {
+        // READY and META both carry the full login list of this server
+        4 | 5 => {
             if let Some(body) = signal.body {
                 match serde_json::from_value::<Logins>(body) {
-                    Ok(logins) => {
-                        let mut bots = bots.write().await;
-                        for login in logins.logins {
-                            bots.insert(
-                                BotId {
-                                    platform: login.platform.unwrap(),
-                                    id: login.self_id.unwrap(),
-                                },
-                                net.clone(),
-                            );
-                        }
-                    }
+                    Ok(logins) => set_logins(bots, net, logins.logins).await,
                     Err(e) => {
                         warn!(target: SATORI, "deserlize logins error:{e}")
                     }
                 }
             }
         }
-        _ => unreachable!(),
+        op => warn!(target: SATORI, "Protocol error: unexpected signal op {op} from server"),
+    }
+}
+
+async fn session<S, A>(
+    s: &Arc<Satori<S, A>>,
+    mut ws_stream: WsStream,
+    bots: &Bots,
+    dispatcher: &Dispatcher,
+    net: &NetSDKConfig,
+    seq: &mut Option<i64>,
+    srx: &mut tokio::sync::broadcast::Receiver<()>,
+) -> SessionEnd
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let mut send_time = tokio::time::Instant::now() + Duration::from_secs(10);
+    let identify = Signal::identfy(net.authorize.as_deref().unwrap_or(""), *seq);
+    if ws_stream.send(identify.to_string().into()).await.is_err() {
+        return SessionEnd::Lost;
+    }
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(send_time) => {
+                if ws_stream.send(Signal::ping().to_string().into()).await.is_err() {
+                    return SessionEnd::Lost;
+                }
+                send_time += Duration::from_secs(10);
+            }
+            data = ws_stream.next() => {
+                trace!(target: SATORI, "receive ws_msg: {:?}" ,data);
+                match data {
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                        Ok(signal) => handle_signal(s, signal, bots, dispatcher, net, seq).await,
+                        Err(e) =>  error!(target: SATORI, "deserialize error: {e} in {text}"),
+                    }
+                    Some(Ok(Message::Ping(d))) => {
+                        if ws_stream.send(Message::Pong(d)).await.is_err() {
+                            return SessionEnd::Lost;
+                        }
+                    }
+                    Some(Ok(Message::Pong(_))) => {}
+                    _ => return SessionEnd::Lost,
+                }
+            }
+            _ = srx.recv() => {
+                ws_stream.send(Message::Close(None)).await.ok();
+                return SessionEnd::Shutdown;
+            }
+        }
+    }
+}
+
+async fn run_websocket<S, A>(
+    s: Arc<Satori<S, A>>,
+    net: NetSDKConfig,
+    bots: Bots,
+    dispatcher: Dispatcher,
+    mut srx: tokio::sync::broadcast::Receiver<()>,
+) where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    let mut seq = None;
+    let mut attempt = 0u32;
+    loop {
+        match connect(&net).await {
+            Ok(ws_stream) => {
+                info!(target:SATORI, "WebSocket connected with ws://{}:{}/v1/events", net.host, net.port);
+                attempt = 0;
+                match session(&s, ws_stream, &bots, &dispatcher, &net, &mut seq, &mut srx).await {
+                    SessionEnd::Shutdown => break,
+                    SessionEnd::Lost => {
+                        warn!(target: SATORI, "WebSocket connection with {}:{} lost", net.host, net.port)
+                    }
+                }
+            }
+            Err(e) => error!(target: SATORI, "connect to {}:{} failed: {e}", net.host, net.port),
+        }
+        if net.reconnect.exhausted(attempt) {
+            error!(target: SATORI, "give up reconnecting to {}:{}", net.host, net.port);
+            break;
+        }
+        set_status(&bots, &net, Status::Reconnect).await;
+        let delay = net.reconnect.delay(attempt);
+        attempt += 1;
+        info!(target: SATORI, "reconnect to {}:{} in {:?}", net.host, net.port, delay);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = srx.recv() => break,
+        }
+    }
+    set_status(&bots, &net, Status::Disconnect).await;
+}
+
+async fn run_webhook<S, A>(
+    s: Arc<Satori<S, A>>,
+    net: NetSDKConfig,
+    bots: Bots,
+    dispatcher: Dispatcher,
+    addr: SocketAddr,
+    path: String,
+    mut srx: tokio::sync::broadcast::Receiver<()>,
+) where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    // axum only accepts paths starting with `/`
+    let path = if path.starts_with('/') {
+        path
+    } else {
+        format!("/{path}")
+    };
+    let net = Arc::new(net);
+    let app = axum::Router::new().route(
+        &path,
+        axum::routing::post({
+            let net = net.clone();
+            let (bots, dispatcher) = (bots.clone(), dispatcher.clone());
+            move |headers, event| webhook_handle(headers, event, s, net, bots, dispatcher)
+        }),
+    );
+    let server = match axum::Server::try_bind(&addr) {
+        Ok(server) => server.serve(app.into_make_service()),
+        Err(e) => {
+            error!(target: SATORI, "bind webhook listener {addr} failed: {e}");
+            return;
+        }
+    };
+    info!(target: SATORI, "Listen webhook in {addr}{path}");
+    // no READY comes in this mode, so the logins are asked for up front
+    match post(&net, "meta", None, "application/json", Body::from("{}")).await {
+        Ok(body) => match serde_json::from_str::<Logins>(&body) {
+            Ok(logins) => set_logins(&bots, &net, logins.logins).await,
+            Err(e) => warn!(target: SATORI, "deserlize meta error:{e}"),
+        },
+        Err(e) => warn!(target: SATORI, "fetch meta from {}:{} failed: {e}", net.host, net.port),
+    }
+    tokio::select! {
+        r = server => {
+            if let Err(e) = r {
+                error!(target: SATORI, "webhook listener {addr} stopped: {e}");
+            }
+        }
+        _ = srx.recv() => {}
+    }
+    set_status(&bots, &net, Status::Disconnect).await;
+}
+
+async fn webhook_handle<S, A>(
+    headers: HeaderMap,
+    Json(event): Json<Event>,
+    s: Arc<Satori<S, A>>,
+    net: Arc<NetSDKConfig>,
+    bots: Bots,
+    dispatcher: Dispatcher,
+) -> StatusCode
+where
+    S: SdkT + Send + Sync + 'static,
+    A: AppT + Send + Sync + 'static,
+{
+    if let Some(token) = &net.authorize {
+        let bearer = headers
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !bearer.is_some_and(|b| token_eq(b, token)) {
+            warn!(target: SATORI, "webhook request with invalid token rejected");
+            return StatusCode::UNAUTHORIZED;
+        }
+    }
+    // without READY the logins are learned from the events themselves
+    let bot = BotId {
+        platform: event.platform.clone(),
+        id: event.self_id.clone(),
+    };
+    let login = event.login.clone();
+    bots.write().await.entry(bot).or_insert_with(|| {
+        let login = login.unwrap_or(Login {
+            sn: None,
+            adapter: None,
+            user: None,
+            self_id: Some(event.self_id.clone()),
+            platform: Some(event.platform.clone()),
+            status: Status::Online,
+            features: vec![],
+            proxy_urls: vec![],
+        });
+        (net.as_ref().clone(), login)
+    });
+    info!(target: SATORI, "receive event: {:?}", event);
+    match dispatcher.try_dispatch(&s, event) {
+        Ok(()) => StatusCode::OK,
+        // the server is expected to retry
+        Err(e) => {
+            warn!(target: SATORI, "{e}");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+async fn post(
+    net: &NetSDKConfig,
+    path: &str,
+    bot: Option<&BotId>,
+    content_type: &str,
+    body: Body,
+) -> Result<String, CallApiError> {
+    let mut req = Builder::new()
+        .method("POST")
+        .uri(format!("http://{}:{}/v1/{}", net.host, net.port, path))
+        .header("Content-Type", content_type);
+    if let Some(bot) = bot {
+        req = req
+            .header("X-Platform", &bot.platform)
+            .header("X-Self-ID", &bot.id);
+    }
+    if let Some(token) = &net.authorize {
+        req = req.header("Authorization", format!("Bearer {}", token));
+    }
+    let req = req
+        .body(body)
+        .map_err(|e| CallApiError::Protocol(format!("build request failed: {e}")))?;
+    trace!(target: SATORI,"Request:{:?}", req);
+    let client = Client::new();
+    let resp = tokio::time::timeout(net.api_timeout, async {
+        let resp = client.request(req).await?;
+        trace!(target: SATORI,"Response:{:?}", resp);
+        let status = resp.status();
+        hyper::body::to_bytes(resp).await.map(|body| (status, body))
+    })
+    .await;
+    let (status, body) = match resp {
+        Ok(r) => r.map_err(CallApiError::Transport)?,
+        Err(_) => return Err(CallApiError::Timeout),
+    };
+    let body = String::from_utf8(body.to_vec())
+        .map_err(|e| CallApiError::Protocol(format!("response body is not utf-8: {e}")))?;
+    if status.is_success() {
+        Ok(body)
+    } else {
+        Err(CallApiError::from_status(status.as_u16(), body))
+    }
+}
+
+fn multipart_body(boundary: &str, files: &[UploadFile]) -> Vec<u8> {
+    let quote = |s: &str| s.replace('"', "%22").replace(['\r', '\n'], " ");
+    let mut body = vec![];
+    for file in files {
+        body.extend(format!("--{boundary}\r\n").bytes());
+        body.extend(
+            format!(
+                "Content-Disposition: form-data; name=\"{}\"",
+                quote(&file.name)
+            )
+            .bytes(),
+        );
+        if let Some(filename) = &file.filename {
+            body.extend(format!("; filename=\"{}\"", quote(filename)).bytes());
+        }
+        let content_type = file
+            .content_type
+            .as_deref()
+            .unwrap_or("application/octet-stream");
+        body.extend(format!("\r\nContent-Type: {}\r\n\r\n", quote(content_type)).bytes());
+        body.extend(&file.data);
+        body.extend(b"\r\n");
+    }
+    body.extend(format!("--{boundary}--\r\n").bytes());
+    body
+}
+
+impl NetSDK {
+    /// A `NetSDK` handing events to the app as set by `config`, the default
+    /// keeps the order within each channel.
+    pub fn with_dispatch(config: DispatchConfig) -> Self {
+        Self {
+            bots: Default::default(),
+            dispatcher: Dispatcher::new(config),
+        }
+    }
+
+    async fn request(
+        &self,
+        path: &str,
+        bot: &BotId,
+        content_type: &str,
+        body: Body,
+    ) -> Result<String, CallApiError> {
+        let Some(net) = self.bots.read().await.get(bot).map(|(net, _)| net.clone()) else {
+            return Err(CallApiError::NotFound(format!("bot {bot:?} not found")));
+        };
+        post(&net, path, Some(bot), content_type, body).await
     }
 }
 
@@ -90,66 +446,15 @@ impl SdkT for NetSDK {
     {
         let mut joins = vec![];
         for net in config {
-            let mut srx = s.get_stx().subscribe();
+            let srx = s.get_stx().subscribe();
             let s = s.clone();
-            let bots = self.bots.clone();
+            let (bots, dispatcher) = (self.bots.clone(), self.dispatcher.clone());
             joins.push(tokio::spawn(async move {
-                let uri = format!("{}:{}", net.host, net.port);
-                let stream = TcpStream::connect(&uri).await.unwrap(); //todo
-                let (mut ws_stream, _) = client_async(
-                    Builder::new()
-                        .method("GET")
-                        .header("Host", net.host.clone().to_string())
-                        .header("Connection", "Upgrade")
-                        .header("Upgrade", "websocket")
-                        .header("Sec-WebSocket-Version", "13")
-                        .header("Sec-WebSocket-Key", generate_key())
-                        .uri(format!("ws://{uri}/v1/events"))
-                        .body(())
-                        .unwrap(),
-                    stream,
-                )
-                .await
-                .unwrap();
-                info!(target:SATORI, "WebSocket connected with ws://{uri}/v1/events");
-
-                let mut send_time = tokio::time::Instant::now() + Duration::from_secs(10);
-                let mut seq = 0i64;
-                ws_stream
-                    .send(
-                        Signal::identfy(&net.authorize.clone().unwrap_or("".to_string()), seq)
-                            .to_string()
-                            .into(),
-                    )
-                    .await
-                    .unwrap();
-                loop {
-                    tokio::select! {
-                        _ = tokio::time::sleep_until(send_time) => {
-                            ws_stream.send(
-                                    Signal::ping().to_string().into()
-                                ).await.unwrap();
-                            send_time += Duration::from_secs(10);
-                        }
-                        data = ws_stream.next() => {
-                            trace!(target: SATORI, "receive ws_msg: {:?}" ,data);
-                            match data {
-                                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
-                                    Ok(signal) => handle_signal(&s,signal,&bots,&net, &mut seq).await,
-                                    Err(e) =>  error!(target: SATORI, "deserialize error: {e} in {text}"),
-                                }
-                                Some(Ok(Message::Ping(d))) => match ws_stream.send(Message::Pong(d)).await {
-                                    Ok(_) => {}
-                                    Err(_) => break,
-                                }
-                                Some(Ok(Message::Pong(_))) => {}
-                                _ => break,
-                            }
-                        }
-                        _ = srx.recv() => {
-                            ws_stream.send(Message::Close(None)).await.ok();
-                            break;
-                        }
+                match net.mode.clone() {
+                    NetSDKMode::WebSocket => run_websocket(s, net, bots, dispatcher, srx).await,
+                    NetSDKMode::Webhook { host, port, path } => {
+                        let addr = (host, port).into();
+                        run_webhook(s, net, bots, dispatcher, addr, path, srx).await
                     }
                 }
             }));
@@ -157,46 +462,84 @@ impl SdkT for NetSDK {
         joins
     }
     async fn call_api(&self, api: &str, bot: &BotId, data: Value) -> Result<String, CallApiError> {
-        let mut req = Builder::new()
-            .method("POST")
-            .header("Content-Type", "application/json")
-            .header("X-Platform", &bot.platform)
-            .header("X-Self-ID", &bot.id);
-        if let Some(net) = self.bots.read().await.get(bot) {
-            req = req.uri(format!("http://{}:{}/v1/{}", net.host, net.port, api));
-            if let Some(token) = &net.authorize {
-                req = req.header("Authorization", format!("Bearer {}", token));
-            }
-        } else {
-            return Err(CallApiError::NotFound);
+        self.request(api, bot, "application/json", Body::from(data.to_string()))
+            .await
+    }
+    async fn call_internal(
+        &self,
+        method: &str,
+        bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        self.request(
+            &format!("internal/{method}"),
+            bot,
+            "application/json",
+            Body::from(data.to_string()),
+        )
+        .await
+    }
+    async fn upload(
+        &self,
+        bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        let boundary = format!("satori-{:016x}", fastrand::u64(..));
+        let body = multipart_body(&boundary, &files);
+        let content_type = format!("multipart/form-data; boundary={boundary}");
+        let resp = self
+            .request("upload.create", bot, &content_type, Body::from(body))
+            .await?;
+        serde_json::from_str(&resp).map_err(CallApiError::DeserializeFailed)
+    }
+    async fn fetch_resource(&self, url: &str) -> Result<ProxyResource, CallApiError> {
+        let bots = self.bots.read().await;
+        let Some((net, _)) = bots
+            .values()
+            .find(|(_, login)| login.proxy_urls.iter().any(|p| url.starts_with(p.as_str())))
+        else {
+            return Err(CallApiError::Forbidden(format!("{url} is not a proxy url")));
+        };
+        let uri = format!("http://{}:{}/v1/proxy/{url}", net.host, net.port);
+        let (timeout, token) = (net.api_timeout, net.authorize.clone());
+        drop(bots);
+        let mut req = Builder::new().method("GET").uri(uri);
+        if let Some(token) = token {
+            req = req.header("Authorization", format!("Bearer {token}"));
         }
         let req = req
-            .body(Body::from(serde_json::to_string(&data).unwrap()))
-            .unwrap();
-        trace!(target: SATORI,"Request:{:?}", req);
-        let client = Client::new();
-        let resp = client.request(req).await.unwrap();
-        trace!(target: SATORI,"Response:{:?}", resp);
-        match resp.status() {
-            StatusCode::OK => {
-                let body = hyper::body::to_bytes(resp).await.unwrap();
-                Ok(String::from_utf8(body.to_vec()).unwrap())
-            }
-            StatusCode::NOT_FOUND => Err(CallApiError::NotFound),
-            _ => unimplemented!(),
+            .body(Body::empty())
+            .map_err(|e| CallApiError::Protocol(format!("build request failed: {e}")))?;
+        let resp = match tokio::time::timeout(timeout, Client::new().request(req)).await {
+            Ok(resp) => resp.map_err(CallApiError::Transport)?,
+            Err(_) => return Err(CallApiError::Timeout),
+        };
+        let status = resp.status();
+        if !status.is_success() {
+            let body = hyper::body::to_bytes(resp)
+                .await
+                .map_err(CallApiError::Transport)?;
+            return Err(CallApiError::from_status(
+                status.as_u16(),
+                String::from_utf8_lossy(&body).into_owned(),
+            ));
         }
+        let content_type = resp
+            .headers()
+            .get("Content-Type")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+        Ok(ProxyResource {
+            content_type,
+            body: resp.into_body(),
+        })
     }
     async fn get_logins(&self) -> Vec<Login> {
         self.bots
             .read()
             .await
-            .keys()
-            .map(|bot| Login {
-                user: None,
-                self_id: Some(bot.id.clone()),
-                platform: Some(bot.platform.clone()),
-                status: crate::Status::Online,
-            })
+            .values()
+            .map(|(_, login)| login.clone())
             .collect()
     }
 }
@@ -0,0 +1,186 @@
+use super::ReconnectConfig;
+use crate::{CallApiError, Event, SATORI};
+
+use hyper::http::uri::InvalidUri;
+use hyper::{Body, Client, Request, Uri};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{error, info, trace, warn};
+
+/// A target the NetApp POSTs every event to. Only absolute `http` urls are
+/// accepted.
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub token: Option<String>,
+    pub retry: ReconnectConfig,
+    /// Time allowed for one delivery attempt, a timed out attempt is retried.
+    pub timeout: Duration,
+    /// Events waiting for delivery before new ones are dropped.
+    pub queue_size: usize,
+}
+
+impl WebhookConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            token: None,
+            retry: ReconnectConfig {
+                max_retries: Some(5),
+                ..Default::default()
+            },
+            timeout: Duration::from_secs(10),
+            queue_size: 1024,
+        }
+    }
+}
+
+/// Why a webhook url was refused.
+#[derive(Debug)]
+pub(super) enum InvalidUrl {
+    Parse(InvalidUri),
+    Unsupported,
+}
+
+impl std::fmt::Display for InvalidUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidUrl::Parse(e) => write!(f, "{e}"),
+            InvalidUrl::Unsupported => write!(f, "only absolute http urls are supported"),
+        }
+    }
+}
+
+/// Running webhook deliveries keyed by url. Each target has its own queue and
+/// worker so events reach a target in order, and a slow target does not hold
+/// back the others.
+#[derive(Default)]
+pub(super) struct Webhooks {
+    targets: RwLock<HashMap<String, mpsc::Sender<Event>>>,
+}
+
+impl Webhooks {
+    pub(super) async fn add(
+        &self,
+        config: WebhookConfig,
+        stx: &broadcast::Sender<()>,
+    ) -> Result<(), InvalidUrl> {
+        let uri = config.url.parse::<Uri>().map_err(InvalidUrl::Parse)?;
+        // deliveries go through a plain `HttpConnector`
+        if uri.scheme_str() != Some("http") || uri.authority().is_none() {
+            return Err(InvalidUrl::Unsupported);
+        }
+        let (tx, rx) = mpsc::channel(config.queue_size.max(1));
+        info!(target: SATORI, "webhook {} registered", config.url);
+        tokio::spawn(deliver(config.clone(), uri, rx, stx.subscribe()));
+        // replacing an existing target drops its sender, the old worker stops
+        // once it has drained its queue
+        self.targets.write().await.insert(config.url, tx);
+        Ok(())
+    }
+
+    pub(super) async fn remove(&self, url: &str) -> bool {
+        let removed = self.targets.write().await.remove(url).is_some();
+        if removed {
+            info!(target: SATORI, "webhook {url} removed");
+        }
+        removed
+    }
+
+    pub(super) async fn urls(&self) -> Vec<String> {
+        self.targets.read().await.keys().cloned().collect()
+    }
+
+    pub(super) async fn send(&self, event: &Event) {
+        for (url, tx) in self.targets.read().await.iter() {
+            if tx.try_send(event.clone()).is_err() {
+                warn!(target: SATORI, "webhook {url} queue is full, drop event {}", event.sn);
+            }
+        }
+    }
+}
+
+async fn deliver(
+    config: WebhookConfig,
+    uri: Uri,
+    mut rx: mpsc::Receiver<Event>,
+    mut srx: broadcast::Receiver<()>,
+) {
+    let client = Client::new();
+    loop {
+        let event = tokio::select! {
+            event = rx.recv() => match event {
+                Some(event) => event,
+                None => return,
+            },
+            _ = srx.recv() => return,
+        };
+        let body = match serde_json::to_string(&event) {
+            Ok(body) => body,
+            Err(e) => {
+                error!(target: SATORI, "serialize event {} failed: {e}", event.sn);
+                continue;
+            }
+        };
+        let mut attempt = 0;
+        loop {
+            match post(&client, &config, &uri, &event, &body).await {
+                Ok(()) => break,
+                Err(e) if config.retry.exhausted(attempt) => {
+                    error!(target: SATORI, "webhook {} give up event {}: {e}", config.url, event.sn);
+                    break;
+                }
+                Err(e) => {
+                    let delay = config.retry.delay(attempt);
+                    attempt += 1;
+                    warn!(target: SATORI, "webhook {} failed: {e}, retry in {delay:?}", config.url);
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = srx.recv() => return,
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn post(
+    client: &Client<hyper::client::HttpConnector>,
+    config: &WebhookConfig,
+    uri: &Uri,
+    event: &Event,
+    body: &str,
+) -> Result<(), CallApiError> {
+    let mut req = Request::post(uri.clone())
+        .header("Content-Type", "application/json")
+        .header("X-Platform", &event.platform)
+        .header("X-Self-ID", &event.self_id);
+    if let Some(token) = &config.token {
+        req = req.header("Authorization", format!("Bearer {token}"));
+    }
+    let req = req
+        .body(Body::from(body.to_owned()))
+        .map_err(|e| CallApiError::Protocol(format!("build request failed: {e}")))?;
+    let resp = tokio::time::timeout(config.timeout, async {
+        let resp = client.request(req).await?;
+        trace!(target: SATORI, "webhook response: {:?}", resp);
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(None);
+        }
+        hyper::body::to_bytes(resp)
+            .await
+            .map(|body| Some((status, body)))
+    })
+    .await;
+    match resp {
+        Ok(Ok(None)) => Ok(()),
+        Ok(Ok(Some((status, body)))) => Err(CallApiError::from_status(
+            status.as_u16(),
+            String::from_utf8_lossy(&body).into_owned(),
+        )),
+        Ok(Err(e)) => Err(CallApiError::Transport(e)),
+        Err(_) => Err(CallApiError::Timeout),
+    }
+}
@@ -0,0 +1,71 @@
+use futures_util::stream::{self, Stream};
+use std::collections::VecDeque;
+use std::future::Future;
+
+use crate::{BidiList, CallApiError, Direction, List};
+
+struct State<T, F> {
+    fetch: F,
+    buf: VecDeque<T>,
+    // `None` once the last page has been fetched
+    next: Option<Option<String>>,
+}
+
+/// Turns a list API into a stream of its items. `fetch` is called with the
+/// `next` token of the previous page (`None` for the first one), pages are
+/// only requested once the items of the current one are consumed. The stream
+/// ends after the last page or the first error.
+pub fn paginate<T, F, Fut>(fetch: F) -> impl Stream<Item = Result<T, CallApiError>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<List<T>, CallApiError>>,
+{
+    let state = State {
+        fetch,
+        buf: VecDeque::new(),
+        next: Some(None),
+    };
+    stream::unfold(state, |mut st| async move {
+        loop {
+            if let Some(item) = st.buf.pop_front() {
+                return Some((Ok(item), st));
+            }
+            let token = st.next.take()?;
+            match (st.fetch)(token.clone()).await {
+                Ok(list) => {
+                    st.buf.extend(list.data);
+                    // a page pointing at itself would never end
+                    st.next = list.next.filter(|n| Some(n) != token.as_ref()).map(Some);
+                }
+                Err(e) => return Some((Err(e), st)),
+            }
+        }
+    })
+}
+
+/// Like [`paginate`] for bidirectional lists, following `prev` when walking
+/// `Before` and `next` when walking `After`. `Around` yields a single page.
+pub fn paginate_bidi<T, F, Fut>(
+    direction: Direction,
+    mut fetch: F,
+) -> impl Stream<Item = Result<T, CallApiError>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<BidiList<T>, CallApiError>>,
+{
+    paginate(move |token| {
+        let fut = fetch(token);
+        async move {
+            let list = fut.await?;
+            let next = match direction {
+                Direction::Before => list.prev,
+                Direction::After => list.next,
+                Direction::Around => None,
+            };
+            Ok(List {
+                data: list.data,
+                next,
+            })
+        }
+    })
+}
@@ -0,0 +1,228 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+use tracing::error;
+
+use crate::element::{self, Element};
+use crate::{
+    ApiCaller, AppT, Bot, BotId, CallApiError, Channel, Event, Guild, GuildMember, Message, Satori,
+    SdkT, User, SATORI,
+};
+
+/// An event together with the bot that received it.
+#[derive(Clone)]
+pub struct Session {
+    pub event: Event,
+    id: BotId,
+    caller: Arc<dyn ApiCaller>,
+    prompts: Arc<Prompts>,
+    stx: broadcast::Sender<()>,
+}
+
+impl Session {
+    pub fn new<S, A>(s: &Arc<Satori<S, A>>, event: Event) -> Self
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let id = BotId {
+            id: event.self_id.clone(),
+            platform: event.platform.clone(),
+        };
+        Self {
+            event,
+            id,
+            caller: s.clone(),
+            prompts: s.prompts.clone(),
+            stx: s.get_stx(),
+        }
+    }
+
+    pub fn bot_id(&self) -> &BotId {
+        &self.id
+    }
+
+    pub fn bot(&self) -> Bot<'_, dyn ApiCaller> {
+        self.caller.bot(&self.id)
+    }
+
+    pub fn channel(&self) -> Option<&Channel> {
+        self.event.channel.as_ref()
+    }
+
+    pub fn guild(&self) -> Option<&Guild> {
+        self.event.guild.as_ref()
+    }
+
+    pub fn author(&self) -> Option<&User> {
+        self.event.user.as_ref()
+    }
+
+    pub fn member(&self) -> Option<&GuildMember> {
+        self.event.member.as_ref()
+    }
+
+    pub fn message(&self) -> Option<&Message> {
+        self.event.message.as_ref()
+    }
+
+    /// Whether the event was caused by the bot itself.
+    pub fn is_self(&self) -> bool {
+        self.author().is_some_and(|u| u.id == self.event.self_id)
+    }
+
+    fn channel_id(&self) -> Result<&str, CallApiError> {
+        self.channel()
+            .map(|c| c.id.as_str())
+            .ok_or_else(|| CallApiError::BadRequest("event has no channel".to_owned()))
+    }
+
+    fn message_id(&self) -> Result<&str, CallApiError> {
+        self.message()
+            .map(|m| m.id.as_str())
+            .ok_or_else(|| CallApiError::BadRequest("event has no message".to_owned()))
+    }
+
+    /// Sends `content` to another channel.
+    pub async fn send(
+        &self,
+        channel_id: &str,
+        content: &str,
+    ) -> Result<Vec<Message>, CallApiError> {
+        self.bot().message_create(channel_id, content).await
+    }
+
+    /// Sends `content` to the channel of the event.
+    pub async fn reply(&self, content: &str) -> Result<Vec<Message>, CallApiError> {
+        self.send(self.channel_id()?, content).await
+    }
+
+    /// Like [`Session::reply`], quoting the message of the event.
+    pub async fn quote_reply(&self, content: &str) -> Result<Vec<Message>, CallApiError> {
+        let quote = element::stringify(&[Element::quote(self.message_id()?)]);
+        self.reply(&format!("{quote}{content}")).await
+    }
+
+    pub async fn react(&self, emoji: &str) -> Result<(), CallApiError> {
+        self.bot()
+            .reaction_create(self.channel_id()?, self.message_id()?, emoji)
+            .await
+    }
+
+    /// Deletes the message of the event.
+    pub async fn delete(&self) -> Result<(), CallApiError> {
+        self.bot()
+            .message_delete(self.channel_id()?, self.message_id()?)
+            .await
+    }
+
+    /// Matches later messages from the same user in the same channel, the
+    /// default of [`Session::prompt`].
+    pub fn same_author(&self) -> impl Fn(&Event) -> bool + Send + Sync + 'static {
+        let (id, user, channel) = (
+            self.id.clone(),
+            self.author().map(|u| u.id.clone()),
+            self.channel().map(|c| c.id.clone()),
+        );
+        move |e| {
+            e.ty == "message-created"
+                && e.platform == id.platform
+                && e.self_id == id.id
+                && e.user.as_ref().map(|u| &u.id) == user.as_ref()
+                && e.channel.as_ref().map(|c| &c.id) == channel.as_ref()
+        }
+    }
+
+    /// Waits for the next message from the same user in the same channel.
+    pub async fn prompt(&self, timeout: Duration) -> Option<Session> {
+        self.prompt_with(timeout, self.same_author()).await
+    }
+
+    /// Waits for the next event matching `matcher`. The event is taken by
+    /// this session and not passed on to the app. Gives `None` on timeout or
+    /// shutdown.
+    pub async fn prompt_with<F>(&self, timeout: Duration, matcher: F) -> Option<Session>
+    where
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        let mut srx = self.stx.subscribe();
+        let rx = self.prompts.register(Arc::new(matcher));
+        let event = tokio::select! {
+            event = rx => event.ok()?,
+            _ = tokio::time::sleep(timeout) => return None,
+            _ = srx.recv() => return None,
+        };
+        let id = BotId {
+            id: event.self_id.clone(),
+            platform: event.platform.clone(),
+        };
+        Some(Session {
+            event,
+            id,
+            ..self.clone()
+        })
+    }
+}
+
+type Matcher = Arc<dyn Fn(&Event) -> bool + Send + Sync>;
+
+/// Sessions waiting for a prompt answer.
+#[derive(Default)]
+pub(crate) struct Prompts {
+    next: AtomicU64,
+    waiters: Mutex<Vec<Waiter>>,
+}
+
+struct Waiter {
+    id: u64,
+    matcher: Matcher,
+    tx: oneshot::Sender<Event>,
+}
+
+impl Prompts {
+    fn waiters(&self) -> MutexGuard<'_, Vec<Waiter>> {
+        // no user code runs under the lock, the list stays valid
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn register(&self, matcher: Matcher) -> oneshot::Receiver<Event> {
+        let (tx, rx) = oneshot::channel();
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        self.waiters().push(Waiter { id, matcher, tx });
+        rx
+    }
+
+    /// Hands `event` to the first waiter it matches, gives it back if there
+    /// is none. Matchers run without the lock, a panicking one never matches.
+    pub(crate) fn offer(&self, event: Event) -> Option<Event> {
+        let waiting: Vec<_> = {
+            let mut waiters = self.waiters();
+            // waiters that timed out or were cancelled
+            waiters.retain(|w| !w.tx.is_closed());
+            waiters.iter().map(|w| (w.id, w.matcher.clone())).collect()
+        };
+        for (id, matcher) in waiting {
+            let matched = panic::catch_unwind(AssertUnwindSafe(|| matcher(&event)));
+            match matched {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(_) => {
+                    error!(target: SATORI, "prompt matcher panicked on event {}", event.sn);
+                    continue;
+                }
+            }
+            let waiter = {
+                let mut waiters = self.waiters();
+                // answered by another event meanwhile
+                let Some(i) = waiters.iter().position(|w| w.id == id) else {
+                    continue;
+                };
+                waiters.remove(i)
+            };
+            return waiter.tx.send(event).err();
+        }
+        Some(event)
+    }
+}
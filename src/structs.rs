@@ -6,7 +6,9 @@ use serde_repr::{Deserialize_repr, Serialize_repr};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Event {
-    pub id: i64,
+    /// Sequence number of the event, older servers call it `id`.
+    #[serde(flatten, with = "sn_or_id")]
+    pub sn: i64,
     #[serde(rename = "type")]
     pub ty: String,
     pub platform: String,
@@ -19,6 +21,9 @@ pub struct Event {
     pub operator: Option<User>,
     pub role: Option<GuildRole>,
     pub user: Option<User>,
+    pub message: Option<Message>,
+    pub button: Option<Button>,
+    pub argv: Option<Argv>,
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
@@ -36,9 +41,9 @@ pub struct Channel {
 #[repr(u8)]
 pub enum ChannelType {
     Text = 0,
-    Voice = 1,
+    Direct = 1,
     Category = 2,
-    Direct = 3,
+    Voice = 3,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -50,16 +55,24 @@ pub struct Guild {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Login {
+    pub sn: Option<i64>,
+    pub adapter: Option<String>,
     pub user: Option<User>,
     pub self_id: Option<String>,
     pub platform: Option<String>,
     pub status: Status,
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Url prefixes clients may fetch through `/v1/proxy/`.
+    #[serde(default)]
+    pub proxy_urls: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct User {
     pub id: String,
     pub name: Option<String>,
+    pub nick: Option<String>,
     pub avatar: Option<String>,
     pub is_bot: Option<bool>,
 }
@@ -77,11 +90,64 @@ pub enum Status {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GuildMember {
     pub user: Option<User>,
-    pub name: Option<String>,
+    /// Older servers call it `name`.
+    #[serde(flatten, with = "nick_or_name")]
+    pub nick: Option<String>,
     pub avatar: Option<String>,
     pub joined_at: Option<i64>,
 }
 
+// Fields renamed by the spec are read under both names, the new one winning,
+// and written under both so older peers keep working.
+mod sn_or_id {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Sn {
+        sn: Option<i64>,
+        id: Option<i64>,
+    }
+
+    pub fn serialize<S: Serializer>(sn: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        let sn = Some(*sn);
+        Sn { sn, id: sn }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        let Sn { sn, id } = Sn::deserialize(deserializer)?;
+        sn.or(id).ok_or_else(|| D::Error::missing_field("sn"))
+    }
+}
+
+mod nick_or_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Nick {
+        nick: Option<String>,
+        name: Option<String>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        nick: &Option<String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let name = nick.clone();
+        Nick {
+            nick: nick.clone(),
+            name,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        let Nick { nick, name } = Nick::deserialize(deserializer)?;
+        Ok(nick.or(name))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GuildRole {
     pub id: Option<String>,
@@ -91,11 +157,290 @@ pub struct GuildRole {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Message {
     pub id: String,
+    #[serde(default)]
     pub content: String,
     pub channel: Option<Channel>,
     pub guild: Option<Guild>,
     pub member: Option<GuildMember>,
     pub user: Option<User>,
+    pub quote: Option<Box<Message>>,
     pub created_at: Option<i64>,
     pub updated_at: Option<i64>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Button {
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Argv {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Vec<Value>,
+    #[serde(default)]
+    pub options: HashMap<String, Value>,
+}
+
+/// A file part of an `upload.create` request.
+#[derive(Clone, Debug)]
+pub struct UploadFile {
+    /// Form field name, the response maps it to the uploaded url.
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl UploadFile {
+    pub fn new(name: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            filename: None,
+            content_type: None,
+            data: data.into(),
+        }
+    }
+
+    pub async fn from_path(
+        name: impl Into<String>,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        Ok(Self {
+            name: name.into(),
+            filename: path.file_name().map(|n| n.to_string_lossy().into_owned()),
+            content_type: None,
+            data: tokio::fs::read(path).await?,
+        })
+    }
+}
+
+/// Bytes of a resource served through `/v1/proxy/`.
+pub struct ProxyResource {
+    pub content_type: Option<String>,
+    pub body: hyper::Body,
+}
+
+impl Message {
+    pub fn elements(&self) -> Vec<crate::element::Element> {
+        crate::element::parse(&self.content)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct List<T> {
+    pub data: Vec<T>,
+    pub next: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BidiList<T> {
+    pub data: Vec<T>,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Before,
+    After,
+    Around,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ChannelData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub ty: Option<ChannelType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GuildRoleData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelIdRequest {
+    pub channel_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildNextRequest {
+    pub guild_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelCreateRequest {
+    pub guild_id: String,
+    pub data: ChannelData,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelUpdateRequest {
+    pub channel_id: String,
+    pub data: ChannelData,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChannelMuteRequest {
+    pub channel_id: String,
+    pub duration: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserChannelCreateRequest {
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guild_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildIdRequest {
+    pub guild_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NextRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApproveRequest {
+    pub message_id: String,
+    pub approve: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildMemberRequest {
+    pub guild_id: String,
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildMemberKickRequest {
+    pub guild_id: String,
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permanent: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildMemberMuteRequest {
+    pub guild_id: String,
+    pub user_id: String,
+    pub duration: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildMemberRoleRequest {
+    pub guild_id: String,
+    pub user_id: String,
+    pub role_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildRoleCreateRequest {
+    pub guild_id: String,
+    pub role: GuildRoleData,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildRoleUpdateRequest {
+    pub guild_id: String,
+    pub role_id: String,
+    pub role: GuildRoleData,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildRoleDeleteRequest {
+    pub guild_id: String,
+    pub role_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageCreateRequest {
+    pub channel_id: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageIdRequest {
+    pub channel_id: String,
+    pub message_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageUpdateRequest {
+    pub channel_id: String,
+    pub message_id: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageListRequest {
+    pub channel_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<Order>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReactionRequest {
+    pub channel_id: String,
+    pub message_id: String,
+    pub emoji: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReactionDeleteRequest {
+    pub channel_id: String,
+    pub message_id: String,
+    pub emoji: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReactionClearRequest {
+    pub channel_id: String,
+    pub message_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReactionListRequest {
+    pub channel_id: String,
+    pub message_id: String,
+    pub emoji: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserIdRequest {
+    pub user_id: String,
+}
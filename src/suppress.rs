@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::{AppT, Event, EventMiddleware, Satori, SdkT, SATORI};
+
+type Key = (String, String, i64);
+
+/// A middleware dropping messages sent by the bot itself and events already
+/// seen, such as the ones replayed after a reconnect. Both are off until
+/// enabled.
+#[derive(Default)]
+pub struct Suppress {
+    self_messages: bool,
+    window: Option<Duration>,
+    seen: Mutex<Seen>,
+    counters: Arc<SuppressCounters>,
+}
+
+#[derive(Default)]
+struct Seen {
+    keys: HashSet<Key>,
+    order: VecDeque<(Instant, Key)>,
+}
+
+/// Events dropped by a [`Suppress`] so far.
+#[derive(Default, Debug)]
+pub struct SuppressCounters {
+    self_messages: AtomicU64,
+    duplicates: AtomicU64,
+}
+
+impl SuppressCounters {
+    pub fn self_messages(&self) -> u64 {
+        self.self_messages.load(Ordering::Relaxed)
+    }
+
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+}
+
+impl Suppress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops `message-*` events whose user is the bot.
+    pub fn self_messages(mut self) -> Self {
+        self.self_messages = true;
+        self
+    }
+
+    /// Drops events whose `(platform, self_id, sn)` was seen within `window`.
+    pub fn duplicates(mut self, window: Duration) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// Shared with the middleware, so it can be read once the app is moved
+    /// into a `Satori`.
+    pub fn counters(&self) -> Arc<SuppressCounters> {
+        self.counters.clone()
+    }
+
+    fn is_duplicate(&self, event: &Event, window: Duration) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        while let Some((at, _)) = seen.order.front() {
+            if now.duration_since(*at) < window {
+                break;
+            }
+            if let Some((_, key)) = seen.order.pop_front() {
+                seen.keys.remove(&key);
+            }
+        }
+        let key = (event.platform.clone(), event.self_id.clone(), event.sn);
+        if !seen.keys.insert(key.clone()) {
+            return true;
+        }
+        seen.order.push_back((now, key));
+        false
+    }
+}
+
+#[async_trait]
+impl EventMiddleware for Suppress {
+    async fn on_event<S, A>(&self, _s: &Arc<Satori<S, A>>, event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        if self.self_messages
+            && event.ty.starts_with("message-")
+            && event.user.as_ref().is_some_and(|u| u.id == event.self_id)
+        {
+            self.counters.self_messages.fetch_add(1, Ordering::Relaxed);
+            debug!(target: SATORI, "drop self message event {}", event.sn);
+            return None;
+        }
+        if let Some(window) = self.window {
+            if self.is_duplicate(&event, window) {
+                self.counters.duplicates.fetch_add(1, Ordering::Relaxed);
+                debug!(target: SATORI, "drop duplicate event {}", event.sn);
+                return None;
+            }
+        }
+        Some(event)
+    }
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use common::{event, message, StubSdk};
+use satori::command::*;
+use satori::*;
+use serde_json::{json, Value};
+
+fn commands() -> Commands {
+    Commands::new()
+        .prefix("/")
+        .command(
+            Command::new("echo")
+                .alias("say")
+                .description("repeat")
+                .arg("text", ArgKind::Rest)
+                .handler(|c| async move {
+                    c.reply(c.args.str("text").unwrap()).await.unwrap();
+                }),
+        )
+        .command(
+            Command::new("add")
+                .arg("a", ArgKind::Int)
+                .optional_arg("b", ArgKind::Int)
+                .handler(|c| async move {
+                    let sum = c.args.int("a").unwrap() + c.args.int("b").unwrap_or(0);
+                    c.reply(&sum.to_string()).await.unwrap();
+                }),
+        )
+        .command(
+            Command::new("role").description("roles").subcommand(
+                Command::new("give")
+                    .alias("g")
+                    .arg("user", ArgKind::User)
+                    .arg("name", ArgKind::String)
+                    .handler(|c| async move {
+                        let user = c.args.user("user").unwrap();
+                        let name = c.args.str("name").unwrap();
+                        c.reply(&format!("{} {user} {name}", c.command.join(".")))
+                            .await
+                            .unwrap();
+                    }),
+            ),
+        )
+}
+
+fn interaction(argv: Value) -> Event {
+    let fields = json!({"channel": {"id": "c", "type": 0}, "argv": argv});
+    event(1, "interaction/command", fields)
+}
+
+async fn replies(app: Commands, events: Vec<Event>) -> Vec<String> {
+    let sdk = StubSdk::new().answer(|_, _| Ok("[]".to_owned()));
+    let calls = sdk.calls.clone();
+    let s = Satori::new(sdk, app).await;
+    for event in events {
+        s.handle_event(event).await;
+    }
+    let calls = calls.lock().unwrap();
+    calls
+        .iter()
+        .map(|(api, data)| {
+            assert_eq!(api, "message.create");
+            data["content"].as_str().unwrap().to_owned()
+        })
+        .collect()
+}
+
+async fn reply(content: &str) -> Option<String> {
+    let mut replies = replies(commands(), vec![message(1, "c", content)]).await;
+    assert!(replies.len() <= 1);
+    replies.pop()
+}
+
+#[tokio::test]
+async fn prefix_and_mention() {
+    assert_eq!(reply("/echo hi").await.as_deref(), Some("hi"));
+    assert_eq!(reply("  /say hi").await.as_deref(), Some("hi"));
+    assert_eq!(reply("echo hi").await, None);
+    assert_eq!(reply("/nope").await, None);
+    let mention = "<at id=\"B\"/> echo hi";
+    assert_eq!(reply(mention).await.as_deref(), Some("hi"));
+    let both = "<at id=\"B\"/>/add 2 3";
+    assert_eq!(reply(both).await.as_deref(), Some("5"));
+    // someone else was mentioned
+    assert_eq!(reply("<at id=\"X\"/> echo hi").await, None);
+    let app = commands().mention(false);
+    assert!(replies(app, vec![message(1, "c", mention)])
+        .await
+        .is_empty());
+    // no prefix, every message may be a command
+    let app = Commands::new().command(Command::new("ping").handler(|c| async move {
+        c.reply("pong").await.unwrap();
+    }));
+    assert_eq!(replies(app, vec![message(1, "c", "ping")]).await, ["pong"]);
+}
+
+#[tokio::test]
+async fn own_messages_are_ignored() {
+    let mut event = message(1, "c", "/echo hi");
+    event.user.as_mut().unwrap().id = "B".to_owned();
+    assert!(replies(commands(), vec![event]).await.is_empty());
+}
+
+#[tokio::test]
+async fn quoted_and_rest_arguments() {
+    let r = reply("/role give <at id=\"U1\"/> \"big boss\"").await;
+    assert_eq!(r.as_deref(), Some("role.give U1 big boss"));
+    let r = reply("/role give U2 \"unterminated quote").await;
+    assert_eq!(r.as_deref(), Some("role.give U2 unterminated quote"));
+    // the rest keeps elements and escapes as they were sent
+    let r = reply("/echo  hello <b>w</b> &amp; x ").await;
+    assert_eq!(r.as_deref(), Some("hello <b>w</b> &amp; x"));
+}
+
+#[tokio::test]
+async fn subcommands() {
+    let r = reply("/role g U1 admin").await;
+    assert_eq!(r.as_deref(), Some("role.give U1 admin"));
+    // a command without handler answers with its help
+    let help = "/role\nroles\n  /role give &lt;user&gt; &lt;name&gt;";
+    assert_eq!(reply("/role").await.as_deref(), Some(help));
+    assert_eq!(reply("/role nope").await.as_deref(), Some(help));
+    assert_eq!(reply("/help role").await.as_deref(), Some(help));
+    let r = reply("/help role give").await;
+    assert_eq!(
+        r.as_deref(),
+        Some("/role give &lt;user&gt; &lt;name&gt;\naliases: g")
+    );
+    let r = reply("/help").await;
+    assert_eq!(
+        r.as_deref(),
+        Some("/echo &lt;text...&gt; - repeat\n/add &lt;a&gt; [b]\n/role - roles")
+    );
+}
+
+#[tokio::test]
+async fn argument_errors() {
+    assert_eq!(reply("/add 2").await.as_deref(), Some("2"));
+    let r = reply("/add x").await;
+    assert_eq!(
+        r.as_deref(),
+        Some("argument &lt;a&gt; must be an integer\nusage: /add &lt;a&gt; [b]")
+    );
+    let r = reply("/add").await;
+    assert_eq!(
+        r.as_deref(),
+        Some("missing argument &lt;a&gt;\nusage: /add &lt;a&gt; [b]")
+    );
+    let r = reply("/role give <img src=\"x\"/> a").await;
+    assert_eq!(
+        r.as_deref(),
+        Some("argument &lt;user&gt; is invalid\nusage: /role give &lt;user&gt; &lt;name&gt;")
+    );
+}
+
+#[tokio::test]
+async fn interaction_commands() {
+    let events = vec![
+        interaction(json!({"name": "role give", "arguments": ["<at id=\"U9\"/>", "x"]})),
+        interaction(json!({"name": "add", "arguments": [4, "5"]})),
+        interaction(json!({"name": "add", "arguments": []})),
+    ];
+    assert_eq!(
+        replies(commands(), events).await,
+        [
+            "role.give U9 x",
+            "9",
+            "missing argument &lt;a&gt;\nusage: /add &lt;a&gt; [b]"
+        ]
+    );
+}
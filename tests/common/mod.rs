@@ -0,0 +1,160 @@
+//! Stubs shared by the integration tests, each test uses a part of them.
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use satori::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+pub type Calls = Arc<Mutex<Vec<(String, Value)>>>;
+
+type Answer = Box<dyn Fn(&str, &Value) -> Result<String, CallApiError> + Send + Sync>;
+
+/// An sdk recording what it is asked to do. Calls are recorded as
+/// `(api, data)`, internal ones as `("internal {method}", data)` and uploads
+/// as `("upload", names)`. Apis answer `null` unless told otherwise, uploads
+/// answer `internal:{name}` for each file.
+#[derive(Default)]
+pub struct StubSdk {
+    pub calls: Calls,
+    pub uploads: Arc<Mutex<Vec<UploadFile>>>,
+    logins: Vec<Login>,
+    answer: Option<Answer>,
+}
+
+impl StubSdk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bots of platform `p` owned by the sdk.
+    pub fn logins(mut self, ids: &[&str]) -> Self {
+        self.logins = ids.iter().map(|id| login(id)).collect();
+        self
+    }
+
+    pub fn answer<F>(mut self, answer: F) -> Self
+    where
+        F: Fn(&str, &Value) -> Result<String, CallApiError> + Send + Sync + 'static,
+    {
+        self.answer = Some(Box::new(answer));
+        self
+    }
+
+    fn record(&self, api: String, data: Value) {
+        self.calls.lock().unwrap().push((api, data));
+    }
+}
+
+#[async_trait]
+impl SdkT for StubSdk {
+    type Config = ();
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, _config: ()) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.record("start".to_owned(), Value::Null);
+        vec![]
+    }
+    async fn call_api(&self, api: &str, _bot: &BotId, data: Value) -> Result<String, CallApiError> {
+        let answer = match &self.answer {
+            Some(answer) => answer(api, &data),
+            None => Ok("null".to_owned()),
+        };
+        self.record(api.to_owned(), data);
+        answer
+    }
+    async fn get_logins(&self) -> Vec<Login> {
+        self.logins.clone()
+    }
+    async fn upload(
+        &self,
+        _bot: &BotId,
+        files: Vec<UploadFile>,
+    ) -> Result<HashMap<String, String>, CallApiError> {
+        let names: Vec<_> = files.iter().map(|f| f.name.clone()).collect();
+        self.record("upload".to_owned(), json!(names));
+        let urls = names
+            .into_iter()
+            .map(|name| (name.clone(), format!("internal:{name}")))
+            .collect();
+        self.uploads.lock().unwrap().extend(files);
+        Ok(urls)
+    }
+    async fn call_internal(
+        &self,
+        method: &str,
+        _bot: &BotId,
+        data: Value,
+    ) -> Result<String, CallApiError> {
+        self.record(format!("internal {method}"), data);
+        Ok("null".to_owned())
+    }
+}
+
+/// An app passing every event on.
+pub struct Recorder(pub mpsc::UnboundedSender<Event>);
+
+#[async_trait]
+impl AppT for Recorder {
+    type Config = ();
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, _config: ()) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        vec![]
+    }
+    async fn handle_event<S, A>(&self, _s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.0.send(event).ok();
+    }
+}
+
+pub fn login(id: &str) -> Login {
+    serde_json::from_value(json!({"platform": "p", "self_id": id, "status": 1})).unwrap()
+}
+
+pub fn bot_id(id: &str) -> BotId {
+    BotId {
+        platform: "p".to_owned(),
+        id: id.to_owned(),
+    }
+}
+
+/// The bot `B` of platform `p`, which receives every event built here.
+pub fn bot() -> BotId {
+    bot_id("B")
+}
+
+/// An event of `ty` received by [`bot`], with `fields` added.
+pub fn event(sn: i64, ty: &str, fields: Value) -> Event {
+    let mut event = json!({
+        "sn": sn, "type": ty, "platform": "p", "self_id": "B", "timestamp": 0
+    });
+    event
+        .as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    serde_json::from_value(event).unwrap()
+}
+
+/// A `message-created` sent by user `u` to `channel`.
+pub fn message(sn: i64, channel: &str, content: &str) -> Event {
+    event(
+        sn,
+        "message-created",
+        json!({
+            "channel": {"id": channel, "type": 0},
+            "user": {"id": "u"},
+            "message": {"id": sn.to_string(), "content": content}
+        }),
+    )
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use async_trait::async_trait;
+use common::{message, StubSdk};
+use satori::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+
+/// Reports `channel sn` of every event handled. Handlers wait for a permit
+/// of `gate`, panic on `panic` and prompt on `ask`.
+struct App {
+    tx: mpsc::UnboundedSender<String>,
+    gate: Arc<Semaphore>,
+    running: Arc<Running>,
+}
+
+/// Handlers waiting at the gate, now and at most.
+#[derive(Default)]
+struct Running(AtomicUsize, AtomicUsize);
+
+struct Harness {
+    gate: Arc<Semaphore>,
+    rx: mpsc::UnboundedReceiver<String>,
+    running: Arc<Running>,
+    s: Arc<Satori<StubSdk, App>>,
+}
+
+async fn harness(open: bool) -> Harness {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let gate = Arc::new(Semaphore::new(if open {
+        Semaphore::MAX_PERMITS
+    } else {
+        0
+    }));
+    let running = Arc::new(Running::default());
+    let app = App {
+        tx,
+        gate: gate.clone(),
+        running: running.clone(),
+    };
+    let s = Satori::new(StubSdk::new(), app).await;
+    Harness {
+        gate,
+        rx,
+        running,
+        s,
+    }
+}
+
+#[async_trait]
+impl AppT for App {
+    type Config = ();
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, _config: ()) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        vec![]
+    }
+    async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let running = self.running.0.fetch_add(1, Ordering::SeqCst) + 1;
+        self.running.1.fetch_max(running, Ordering::SeqCst);
+        self.gate.acquire().await.unwrap().forget();
+        let session = Session::new(s, event);
+        let channel = session.channel().unwrap().id.clone();
+        let content = session.message().unwrap().content.clone();
+        self.running.0.fetch_sub(1, Ordering::SeqCst);
+        match content.as_str() {
+            "panic" => panic!("handler failed"),
+            "ask" => {
+                let answer = session.prompt(Duration::from_secs(5)).await;
+                let answer = answer.map(|a| a.message().unwrap().content.clone());
+                self.tx.send(format!("{channel} answer {answer:?}")).ok();
+            }
+            _ => {
+                self.tx.send(format!("{channel} {}", session.event.sn)).ok();
+            }
+        }
+    }
+}
+
+async fn recv(rx: &mut mpsc::UnboundedReceiver<String>, n: usize) -> Vec<String> {
+    let mut got = vec![];
+    for _ in 0..n {
+        let next = tokio::time::timeout(Duration::from_secs(5), rx.recv());
+        got.push(next.await.unwrap().unwrap());
+    }
+    got
+}
+
+fn config(concurrency: usize, queue_size: usize) -> Dispatcher {
+    Dispatcher::new(DispatchConfig {
+        concurrency,
+        key: DispatchKey::Channel,
+        queue_size,
+        max_pending: 4096,
+    })
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn keeps_order_per_key() {
+    let Harness { mut rx, s, .. } = harness(true).await;
+    let dispatcher = config(4, 256);
+    for sn in 0..200 {
+        let channel = ["a", "b", "c"][sn as usize % 3];
+        dispatcher.dispatch(&s, message(sn, channel, "")).await;
+    }
+    let got = recv(&mut rx, 200).await;
+    for channel in ["a", "b", "c"] {
+        let sns: Vec<i64> = got
+            .iter()
+            .filter_map(|g| g.strip_prefix(&format!("{channel} ")))
+            .map(|sn| sn.parse().unwrap())
+            .collect();
+        assert!(sns.windows(2).all(|w| w[0] < w[1]), "{channel}: {sns:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn limits_concurrency_without_blocking() {
+    let mut h = harness(false).await;
+    let (s, rx) = (h.s.clone(), &mut h.rx);
+    let dispatcher = config(2, 1);
+    // returns at once while every handler is stuck, a full queue only drops
+    // events of its own channel
+    dispatcher.dispatch(&s, message(0, "a", "")).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    for sn in 1..3 {
+        dispatcher.dispatch(&s, message(sn, "a", "")).await;
+    }
+    for channel in ["b", "c", "d"] {
+        dispatcher.dispatch(&s, message(9, channel, "")).await;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(h.running.0.load(Ordering::SeqCst), 2);
+    h.gate.add_permits(Semaphore::MAX_PERMITS >> 1);
+    let mut got = recv(rx, 5).await;
+    got.sort();
+    assert_eq!(got, ["a 0", "a 1", "b 9", "c 9", "d 9"]);
+    assert_eq!(h.running.1.load(Ordering::SeqCst), 2);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn waits_while_full() {
+    let mut h = harness(false).await;
+    let (s, rx) = (h.s.clone(), &mut h.rx);
+    let dispatcher = Dispatcher::new(DispatchConfig {
+        concurrency: 1,
+        max_pending: 2,
+        ..Default::default()
+    });
+    // events waiting for a permit count as much as running ones
+    dispatcher.try_dispatch(&s, message(0, "a", "")).unwrap();
+    dispatcher.try_dispatch(&s, message(1, "b", "")).unwrap();
+    let full = dispatcher.try_dispatch(&s, message(2, "c", ""));
+    assert_eq!(full.unwrap_err().0.sn, 2);
+    let wait = dispatcher.dispatch(&s, message(3, "c", ""));
+    tokio::pin!(wait);
+    let waited = tokio::time::timeout(Duration::from_millis(50), &mut wait);
+    assert!(waited.await.is_err());
+    assert_eq!(h.running.1.load(Ordering::SeqCst), 1);
+    h.gate.add_permits(Semaphore::MAX_PERMITS >> 1);
+    wait.await;
+    let mut got = recv(rx, 3).await;
+    got.sort();
+    assert_eq!(got, ["a 0", "b 1", "c 3"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn survives_panicking_handlers() {
+    let Harness { mut rx, s, .. } = harness(true).await;
+    let dispatcher = config(1, 256);
+    dispatcher.dispatch(&s, message(1, "a", "panic")).await;
+    dispatcher.dispatch(&s, message(2, "a", "")).await;
+    assert_eq!(recv(&mut rx, 1).await, ["a 2"]);
+    // the channel is served again once its queue emptied
+    dispatcher.dispatch(&s, message(3, "a", "panic")).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    dispatcher.dispatch(&s, message(4, "a", "")).await;
+    dispatcher.dispatch(&s, message(5, "b", "")).await;
+    let mut got = recv(&mut rx, 2).await;
+    got.sort();
+    assert_eq!(got, ["a 4", "b 5"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn answers_prompts_when_saturated() {
+    let Harness { mut rx, s, .. } = harness(true).await;
+    // the only permit is held by the handler waiting for its answer, which
+    // comes through the same channel
+    let dispatcher = config(1, 256);
+    dispatcher.dispatch(&s, message(1, "a", "ask")).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    dispatcher.dispatch(&s, message(2, "a", "yes")).await;
+    dispatcher.dispatch(&s, message(3, "a", "")).await;
+    assert_eq!(recv(&mut rx, 2).await, ["a answer Some(\"yes\")", "a 3"]);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use async_trait::async_trait;
+use common::{bot_id, event, StubSdk};
+use satori::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// An sdk owning the bot `name`, answering with its name.
+fn sdk(name: &'static str) -> StubSdk {
+    StubSdk::new()
+        .logins(&[name])
+        .answer(move |api, _| Ok(json!(format!("{name} {api}")).to_string()))
+}
+
+struct App(&'static str, mpsc::UnboundedSender<String>);
+
+#[async_trait]
+impl AppT for App {
+    type Config = ();
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, _config: ()) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.1.send(format!("start {}", self.0)).ok();
+        vec![]
+    }
+    async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        // calls made by a boxed app reach the boxed sdks
+        let session = Session::new(s, event);
+        let r: String = s
+            .call_api("echo", session.bot_id(), Value::Null)
+            .await
+            .unwrap();
+        self.1
+            .send(format!("{} {}: {r}", self.0, session.event.sn))
+            .ok();
+    }
+}
+
+#[tokio::test]
+async fn dyn_satori() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (a, b) = (sdk("a"), sdk("b"));
+    let started = [a.calls.clone(), b.calls.clone()];
+    let sdks: Vec<Box<dyn DynSdk>> = vec![Box::new(a), Box::new(b)];
+    let apps: Vec<Box<dyn DynApp>> = vec![
+        Box::new(App("x", tx.clone())),
+        Box::new(App("y", tx.clone())),
+    ];
+    let s = DynSatori::new_dyn(Box::new(sdks), Box::new(apps));
+    let sdk_config: Vec<DynConfig> = vec![Box::new(()), Box::new(())];
+    let app_config: Vec<DynConfig> = vec![Box::new(()), Box::new(())];
+    s.start(Box::new(sdk_config), Box::new(app_config)).await;
+    let mut apps: Vec<_> = (0..2).map(|_| rx.try_recv().unwrap()).collect();
+    apps.sort();
+    assert_eq!(apps, ["start x", "start y"]);
+    for calls in &started {
+        assert_eq!(calls.lock().unwrap()[0].0, "start");
+    }
+
+    let r: String = s.call_api("m", &bot_id("b"), Value::Null).await.unwrap();
+    assert_eq!(r, "b m");
+    assert!(matches!(
+        s.call_api::<String>("m", &bot_id("c"), Value::Null).await,
+        Err(CallApiError::NotFound(_))
+    ));
+
+    let mut event = event(9, "x", json!({}));
+    event.self_id = "a".to_owned();
+    s.handle_event(event).await;
+    let mut handled = vec![rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+    handled.sort();
+    assert_eq!(handled, ["x 9: a echo", "y 9: a echo"]);
+}
+
+#[tokio::test]
+async fn dyn_config_mismatch() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let sdk = sdk("a");
+    let calls = sdk.calls.clone();
+    let s = DynSatori::new_dyn(Box::new(sdk), Box::new(App("x", tx)));
+    // the sdk wants a ()
+    s.start(Box::new("bad"), Box::new(())).await;
+    assert_eq!(rx.try_recv().unwrap(), "start x");
+    assert!(rx.try_recv().is_err());
+    assert!(calls.lock().unwrap().is_empty());
+}
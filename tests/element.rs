@@ -0,0 +1,103 @@
+use satori::element::{self, At, Element, Node, Resource};
+
+fn round_trip(markup: &str) {
+    let elements = element::parse(markup);
+    let out = element::stringify(&elements);
+    assert_eq!(out, markup);
+    assert_eq!(element::parse(&out), elements);
+}
+
+#[test]
+fn round_trip_typed() {
+    round_trip("hello <at id=\"1\" name=\"x\"/> world");
+    round_trip("<sharp id=\"c\"/><b>bold <i>both</i></b><br/>");
+    round_trip("<a href=\"https://example.com/?a=1&amp;b=2\">link</a>");
+    round_trip("<img src=\"https://example.com/a.png\" width=\"10\" height=\"20\"/>");
+    round_trip("<quote id=\"m\"/>reply");
+    round_trip("<message forward><author id=\"u\" name=\"n\"/>text</message>");
+    round_trip("<button id=\"b\" type=\"action\" text=\"go\"/>");
+}
+
+#[test]
+fn round_trip_other() {
+    round_trip("<custom:tag foo=\"bar\" flag><b>x</b></custom:tag>");
+    round_trip("<at id=\"1\" unknown=\"y\"/>");
+    round_trip("<img/>");
+}
+
+#[test]
+fn round_trip_escapes() {
+    round_trip("a &lt; b &amp;&amp; c &gt; d");
+    round_trip("<at name=\"&quot;quoted&quot; &lt;x&gt;\"/>");
+    let elements = element::parse("&#x41;&#66;&apos;&unknown;");
+    assert_eq!(elements, vec![Element::text("AB'&unknown;")]);
+}
+
+#[test]
+fn parse_typed() {
+    let elements = element::parse("<at id=\"1\"/><image src=\"s\" cache/>");
+    assert_eq!(
+        elements,
+        vec![
+            Element::at("1"),
+            Element::Img(Resource {
+                src: "s".to_owned(),
+                cache: Some(true),
+                ..Default::default()
+            }),
+        ]
+    );
+    assert_eq!(
+        element::stringify(&elements),
+        "<at id=\"1\"/><img src=\"s\" cache=\"true\"/>"
+    );
+}
+
+#[test]
+fn duplicate_attributes() {
+    let markup = r#"<at id="1" id="2" name="x"/>"#;
+    let elements = element::parse(markup);
+    assert_eq!(
+        elements,
+        vec![Element::Other(Node {
+            tag: "at".to_owned(),
+            attrs: vec![
+                ("id".to_owned(), Some("1".to_owned())),
+                ("id".to_owned(), Some("2".to_owned())),
+                ("name".to_owned(), Some("x".to_owned())),
+            ],
+            children: vec![],
+        })]
+    );
+    assert_eq!(element::stringify(&elements), markup);
+    assert!(matches!(
+        element::parse(r#"<img src="a" src="b"/>"#)[..],
+        [Element::Other(_)]
+    ));
+}
+
+#[test]
+fn lenient() {
+    assert_eq!(element::parse("1 < 2"), vec![Element::text("1 < 2")]);
+    assert_eq!(
+        element::parse("<b>open"),
+        vec![Element::B(vec![Element::text("open")])]
+    );
+    assert_eq!(
+        element::parse("<b>x</i></b>"),
+        vec![Element::B(vec![Element::text("x")])]
+    );
+    assert_eq!(
+        element::parse("<at name=\"n\"/>")[0],
+        Element::At(At {
+            name: Some("n".to_owned()),
+            ..Default::default()
+        })
+    );
+}
+
+#[test]
+fn plain_text() {
+    let elements = element::parse("<p>a<b>b</b><br/>c</p>");
+    assert_eq!(elements[0].plain_text(), "ab\nc");
+}
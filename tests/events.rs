@@ -0,0 +1,148 @@
+mod common;
+
+use common::event;
+use satori::*;
+use serde_json::{json, Value};
+
+/// The event type each variant is built from.
+fn ty(kind: &EventKind) -> &'static str {
+    match kind {
+        EventKind::MessageCreated(_) => "message-created",
+        EventKind::MessageUpdated(_) => "message-updated",
+        EventKind::MessageDeleted(_) => "message-deleted",
+        EventKind::GuildAdded(_) => "guild-added",
+        EventKind::GuildUpdated(_) => "guild-updated",
+        EventKind::GuildRemoved(_) => "guild-removed",
+        EventKind::GuildRequest(_) => "guild-request",
+        EventKind::GuildMemberAdded(_) => "guild-member-added",
+        EventKind::GuildMemberUpdated(_) => "guild-member-updated",
+        EventKind::GuildMemberRemoved(_) => "guild-member-removed",
+        EventKind::GuildMemberRequest(_) => "guild-member-request",
+        EventKind::GuildRoleCreated(_) => "guild-role-created",
+        EventKind::GuildRoleUpdated(_) => "guild-role-updated",
+        EventKind::GuildRoleDeleted(_) => "guild-role-deleted",
+        EventKind::LoginAdded(_) => "login-added",
+        EventKind::LoginRemoved(_) => "login-removed",
+        EventKind::LoginUpdated(_) => "login-updated",
+        EventKind::ReactionAdded(_) => "reaction-added",
+        EventKind::ReactionRemoved(_) => "reaction-removed",
+        EventKind::FriendRequest(_) => "friend-request",
+        EventKind::InteractionButton(_) => "interaction/button",
+        EventKind::InteractionCommand(_) => "interaction/command",
+        EventKind::Internal(_) => "internal",
+        EventKind::Unknown(_) => "unknown",
+    }
+}
+
+/// Every known type with the fields it requires.
+fn required() -> Vec<(&'static str, Value)> {
+    let message = json!({
+        "message": {"id": "m", "content": "hi"},
+        "channel": {"id": "c", "type": 0},
+        "user": {"id": "u"},
+    });
+    let guild = json!({"guild": {"id": "g"}});
+    let member = json!({"guild": {"id": "g"}, "user": {"id": "u"}});
+    let role = json!({"guild": {"id": "g"}, "role": {"id": "r"}});
+    let login = json!({"login": {"platform": "p", "self_id": "B", "status": 1}});
+    let mut types = vec![];
+    for ty in ["message-created", "message-updated", "message-deleted"] {
+        types.push((ty, message.clone()));
+    }
+    for ty in ["reaction-added", "reaction-removed"] {
+        types.push((ty, message.clone()));
+    }
+    for ty in [
+        "guild-added",
+        "guild-updated",
+        "guild-removed",
+        "guild-request",
+    ] {
+        types.push((ty, guild.clone()));
+    }
+    for ty in [
+        "guild-member-added",
+        "guild-member-updated",
+        "guild-member-removed",
+        "guild-member-request",
+    ] {
+        types.push((ty, member.clone()));
+    }
+    for ty in [
+        "guild-role-created",
+        "guild-role-updated",
+        "guild-role-deleted",
+    ] {
+        types.push((ty, role.clone()));
+    }
+    for ty in ["login-added", "login-removed", "login-updated"] {
+        types.push((ty, login.clone()));
+    }
+    types.push(("friend-request", json!({"user": {"id": "u"}})));
+    types.push(("interaction/button", json!({"button": {"id": "b"}})));
+    types.push(("interaction/command", json!({})));
+    types.push(("internal", json!({"_type": "x"})));
+    types
+}
+
+#[test]
+fn typed_variants() {
+    for (name, fields) in required() {
+        let e = event(1, name, fields);
+        assert_eq!(ty(&e.kind()), name);
+        assert_eq!(ty(&EventKind::from(e)), name);
+    }
+    let e = event(1, "message-created", required()[0].1.clone());
+    let EventKind::MessageCreated(m) = e.kind() else {
+        panic!("not a message");
+    };
+    assert_eq!(
+        (m.message.content, m.channel.id, m.user.id),
+        ("hi".to_owned(), "c".to_owned(), "u".to_owned())
+    );
+    assert!(m.guild.is_none() && m.member.is_none());
+}
+
+#[test]
+fn missing_fields_are_unknown() {
+    // `interaction/command` has no required field
+    for (name, fields) in required() {
+        for field in fields.as_object().unwrap().keys() {
+            let mut fields = fields.clone();
+            fields.as_object_mut().unwrap().remove(field);
+            let e = event(1, name, fields);
+            assert_eq!(ty(&e.kind()), "unknown", "{name} without {field}");
+        }
+    }
+}
+
+#[test]
+fn internal_events() {
+    let e = event(1, "internal", json!({"_type": "x", "_data": {"a": 1}}));
+    let EventKind::Internal(internal) = e.kind() else {
+        panic!("not internal");
+    };
+    assert_eq!(internal.ty, "x");
+    assert_eq!(internal.data, json!({"a": 1}));
+    // `_data` is optional
+    let e = event(1, "internal", json!({"_type": "x"}));
+    let EventKind::Internal(internal) = e.kind() else {
+        panic!("not internal");
+    };
+    assert_eq!(internal.data, Value::Null);
+    let e = event(1, "internal", json!({"_type": 1}));
+    assert_eq!(ty(&e.kind()), "unknown");
+}
+
+#[test]
+fn unknown_keeps_the_event() {
+    let fields = json!({"custom": {"a": [1, 2]}, "channel": {"id": "c", "type": 0}});
+    let e = event(1, "something-new", fields);
+    let EventKind::Unknown(unknown) = EventKind::from(e.clone()) else {
+        panic!("known");
+    };
+    assert_eq!(unknown.ty, "something-new");
+    assert_eq!(unknown.extra, e.extra);
+    assert_eq!(unknown.extra["custom"], json!({"a": [1, 2]}));
+    assert_eq!(unknown.channel.unwrap().id, "c");
+}
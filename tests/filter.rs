@@ -0,0 +1,84 @@
+mod common;
+
+use common::{bot_id, event, message, Recorder, StubSdk};
+use satori::filter::*;
+use satori::*;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+fn in_channel(ty: u8) -> Event {
+    event(
+        1,
+        "message-created",
+        json!({"channel": {"id": "c", "type": ty}}),
+    )
+}
+
+#[test]
+fn combinators() {
+    let e = message(1, "c", "hi");
+    let (yes, no) = (all(), !all());
+    assert!(yes.matches(&e) && !no.matches(&e));
+    assert!(yes.clone().and(yes.clone()).matches(&e));
+    assert!(!yes.clone().and(no.clone()).matches(&e));
+    assert!(!no.clone().and(yes.clone()).matches(&e));
+    assert!(yes.clone().or(no.clone()).matches(&e));
+    assert!(no.clone().or(yes.clone()).matches(&e));
+    assert!(!no.clone().or(no.clone()).matches(&e));
+    assert!((!no.clone().and(no)).matches(&e));
+    let f = platform("p").and(channel("c")).and(!user("x"));
+    assert!(f.matches(&e));
+    assert!(!f.matches(&message(2, "d", "hi")));
+}
+
+#[test]
+fn event_fields() {
+    let e = message(1, "c", "hi");
+    assert!(bot(&common::bot()).matches(&e));
+    assert!(!bot(&bot_id("other")).matches(&e));
+    assert!(event_type("message-created").matches(&e));
+    assert!(!event_type("message-deleted").matches(&e));
+    assert!(!guild("g").matches(&e));
+    let e = event(1, "guild-updated", json!({"guild": {"id": "g"}}));
+    assert!(guild("g").matches(&e) && !guild("h").matches(&e));
+}
+
+#[test]
+fn direct_channels() {
+    assert!(!direct().matches(&in_channel(0)));
+    assert!(direct().matches(&in_channel(1)));
+    assert!(!direct().matches(&in_channel(2)));
+    assert!(!direct().matches(&in_channel(3)));
+    assert!(!direct().matches(&event(1, "guild-updated", json!({}))));
+}
+
+#[test]
+fn users() {
+    let e = event(1, "x", json!({"user": {"id": "u", "is_bot": true}}));
+    assert!(user("u").matches(&e) && user_is_bot().matches(&e));
+    // without a user, the user of the member counts
+    let e = event(1, "x", json!({"member": {"user": {"id": "B"}}}));
+    assert!(user("B").matches(&e) && user_is_self().matches(&e));
+    assert!(!user_is_bot().matches(&e));
+    // the user of the event wins over the one of the member
+    let e = event(
+        1,
+        "x",
+        json!({"user": {"id": "u"}, "member": {"user": {"id": "B"}}}),
+    );
+    assert!(user("u").matches(&e) && !user_is_self().matches(&e));
+    assert!(!user("u").matches(&event(1, "x", json!({}))));
+}
+
+#[tokio::test]
+async fn as_middleware() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let app = Recorder(tx).layer(!user_is_self());
+    let s = Satori::new(StubSdk::new(), app).await;
+    let mut own = message(1, "c", "echo");
+    own.user.as_mut().unwrap().id = common::bot().id;
+    s.handle_event(own).await;
+    s.handle_event(message(2, "c", "hi")).await;
+    assert_eq!(rx.recv().await.unwrap().sn, 2);
+    assert!(rx.try_recv().is_err());
+}
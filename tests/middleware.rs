@@ -0,0 +1,228 @@
+mod common;
+
+use async_trait::async_trait;
+use common::{bot, event, StubSdk};
+use satori::*;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+struct App(Log);
+
+#[async_trait]
+impl AppT for App {
+    type Config = ();
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, _config: ()) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        vec![]
+    }
+    async fn handle_event<S, A>(&self, _s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("app {} {}", event.ty, event.sn));
+    }
+}
+
+/// Logs the calls it sees and tags events with its name.
+struct Named(&'static str, Log);
+
+#[async_trait]
+impl EventMiddleware for Named {
+    async fn on_event<S, A>(&self, _s: &Arc<Satori<S, A>>, mut event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        self.1
+            .lock()
+            .unwrap()
+            .push(format!("{} {}", self.0, event.sn));
+        event.ty.push_str(self.0);
+        Some(event)
+    }
+}
+
+#[async_trait]
+impl ApiMiddleware for Named {
+    async fn call_api<S>(&self, call: ApiCall, next: Next<'_, S>) -> Result<String, CallApiError>
+    where
+        S: SdkT + Send + Sync,
+    {
+        self.1
+            .lock()
+            .unwrap()
+            .push(format!("{} {} {}", self.0, call.api, kind(&call)));
+        next.run(call).await
+    }
+}
+
+fn kind(call: &ApiCall) -> &'static str {
+    match call.kind {
+        ApiKind::Standard => "standard",
+        ApiKind::Internal => "internal",
+        ApiKind::Upload(_) => "upload",
+    }
+}
+
+struct DropEven;
+
+#[async_trait]
+impl EventMiddleware for DropEven {
+    async fn on_event<S, A>(&self, _s: &Arc<Satori<S, A>>, event: Event) -> Option<Event>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        (event.sn % 2 == 1).then_some(event)
+    }
+}
+
+/// Answers `cached` itself without reaching the sdk.
+struct Cache;
+
+#[async_trait]
+impl ApiMiddleware for Cache {
+    async fn call_api<S>(&self, call: ApiCall, next: Next<'_, S>) -> Result<String, CallApiError>
+    where
+        S: SdkT + Send + Sync,
+    {
+        match call.api.as_str() {
+            "cached" => Ok(json!("from cache").to_string()),
+            _ => next.run(call).await,
+        }
+    }
+}
+
+struct Retry;
+
+#[async_trait]
+impl ApiMiddleware for Retry {
+    async fn call_api<S>(&self, call: ApiCall, next: Next<'_, S>) -> Result<String, CallApiError>
+    where
+        S: SdkT + Send + Sync,
+    {
+        let mut attempt = 0;
+        loop {
+            match next.run(call.clone()).await {
+                Err(CallApiError::Timeout) if attempt < 3 => attempt += 1,
+                r => return r,
+            }
+        }
+    }
+}
+
+/// Times out the first `failures` calls, then answers with the api name.
+fn stub(failures: u32) -> StubSdk {
+    let failures = AtomicU32::new(failures);
+    StubSdk::new().answer(move |api, _| {
+        match failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |f| f.checked_sub(1)) {
+            Ok(_) => Err(CallApiError::Timeout),
+            Err(_) => Ok(json!(api).to_string()),
+        }
+    })
+}
+
+#[tokio::test]
+async fn event_order_and_drop() {
+    let log = Log::default();
+    let app = App(log.clone())
+        .layer(Named("a", log.clone()))
+        .layer(DropEven)
+        .layer(Named("b", log.clone()));
+    let s = Satori::new(stub(0), app).await;
+    for sn in 1..=2 {
+        s.handle_event(event(sn, "x", json!({}))).await;
+    }
+    // the last layer added sees the event first, a dropped event goes no further
+    assert_eq!(*log.lock().unwrap(), ["b 1", "a 1", "app xba 1", "b 2"]);
+}
+
+#[tokio::test]
+async fn api_order_and_short_circuit() {
+    let log = Log::default();
+    let sdk = stub(0);
+    let calls = sdk.calls.clone();
+    let sdk = sdk
+        .layer(Named("a", log.clone()))
+        .layer(Cache)
+        .layer(Named("b", log.clone()));
+    let s = Satori::new(sdk, App(log.clone())).await;
+    let r: String = s.call_api("m", &bot(), json!(1)).await.unwrap();
+    assert_eq!(r, "m");
+    let r: String = s.call_api("cached", &bot(), json!(1)).await.unwrap();
+    assert_eq!(r, "from cache");
+    assert_eq!(
+        *log.lock().unwrap(),
+        ["b m standard", "a m standard", "b cached standard"]
+    );
+    assert_eq!(*calls.lock().unwrap(), [("m".to_owned(), json!(1))]);
+}
+
+#[tokio::test]
+async fn api_retry() {
+    let log = Log::default();
+    let sdk = stub(2);
+    let calls = sdk.calls.clone();
+    let s = Satori::new(sdk.layer(Retry), App(log.clone())).await;
+    let r: String = s.call_api("m", &bot(), Value::Null).await.unwrap();
+    assert_eq!(r, "m");
+    assert_eq!(calls.lock().unwrap().len(), 3);
+    let s = Satori::new(stub(10).layer(Retry), App(log)).await;
+    assert!(matches!(
+        s.call_api::<String>("m", &bot(), Value::Null).await,
+        Err(CallApiError::Timeout)
+    ));
+}
+
+#[tokio::test]
+async fn upload_and_internal_pass_through_middleware() {
+    let log = Log::default();
+    let sdk = stub(0);
+    let calls = sdk.calls.clone();
+    let sdk = sdk.layer(Named("a", log.clone()));
+    let s = Satori::new(sdk, App(log.clone())).await;
+    let urls = s
+        .upload(&bot(), vec![UploadFile::new("f", b"data".to_vec())])
+        .await
+        .unwrap();
+    assert_eq!(urls["f"], "internal:f");
+    let r: Value = s.call_internal("raw", &bot(), Value::Null).await.unwrap();
+    assert_eq!(r, Value::Null);
+    assert_eq!(
+        *log.lock().unwrap(),
+        ["a upload.create upload", "a raw internal"]
+    );
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [
+            ("upload".to_owned(), json!(["f"])),
+            ("internal raw".to_owned(), Value::Null)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unserializable_data_is_not_sent() {
+    let sdk = stub(0);
+    let calls = sdk.calls.clone();
+    let s = Satori::new(sdk, App(Log::default())).await;
+    let id = bot();
+    // JSON objects need string keys
+    let data = std::collections::BTreeMap::from([((1, 2), 3)]);
+    match s.bot(&id).call::<Value, _>("m", data).await {
+        Err(e @ CallApiError::SerializeFailed(_)) => assert_eq!(e.status(), 500),
+        r => panic!("unexpected {r:?}"),
+    }
+    assert!(calls.lock().unwrap().is_empty());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{bot_id, event, Recorder, StubSdk};
+use satori::*;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn sdks_route_by_login() {
+    let (a, b) = (StubSdk::new().logins(&["A"]), StubSdk::new().logins(&["B"]));
+    let (calls_a, calls_b) = (a.calls.clone(), b.calls.clone());
+    let sdks = (a, b);
+    let logins = SdkT::get_logins(&sdks).await;
+    let ids: Vec<_> = logins
+        .iter()
+        .map(|l| l.self_id.as_deref().unwrap())
+        .collect();
+    assert_eq!(ids, ["A", "B"]);
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let s = Satori::new(sdks, Recorder(tx)).await;
+    s.start(((), ()), ()).await;
+    let _: Value = s.call_api("a", &bot_id("A"), json!(1)).await.unwrap();
+    let _: Value = s.call_api("b", &bot_id("B"), json!(2)).await.unwrap();
+    let _: Value = s.call_internal("x", &bot_id("B"), json!(3)).await.unwrap();
+    let files = vec![UploadFile::new("f", vec![])];
+    let urls = s.upload(&bot_id("A"), files).await.unwrap();
+    assert_eq!(urls["f"], "internal:f");
+    match s.call_api::<Value>("c", &bot_id("C"), json!(4)).await {
+        Err(e @ CallApiError::NotFound(_)) => assert_eq!(e.status(), 404),
+        r => panic!("unexpected {r:?}"),
+    }
+    let calls = |calls: &common::Calls| calls.lock().unwrap().clone();
+    assert_eq!(
+        calls(&calls_a),
+        [
+            ("start".to_owned(), Value::Null),
+            ("a".to_owned(), json!(1)),
+            ("upload".to_owned(), json!(["f"])),
+        ]
+    );
+    assert_eq!(
+        calls(&calls_b),
+        [
+            ("start".to_owned(), Value::Null),
+            ("b".to_owned(), json!(2)),
+            ("internal x".to_owned(), json!(3)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn nested_sdks_route_by_login() {
+    let c = StubSdk::new().logins(&["C"]);
+    let calls_c = c.calls.clone();
+    let sdks = ((StubSdk::new().logins(&["A"]), StubSdk::new()), c);
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let s = Satori::new(sdks, Recorder(tx)).await;
+    let _: Value = s.call_api("c", &bot_id("C"), json!(1)).await.unwrap();
+    assert_eq!(*calls_c.lock().unwrap(), [("c".to_owned(), json!(1))]);
+}
+
+#[tokio::test]
+async fn apps_each_get_every_event() {
+    let (tx0, mut rx0) = mpsc::unbounded_channel();
+    let (tx1, mut rx1) = mpsc::unbounded_channel();
+    let s = Satori::new(StubSdk::new(), (Recorder(tx0), Recorder(tx1))).await;
+    for sn in 1..=2 {
+        s.handle_event(event(sn, "x", json!({}))).await;
+    }
+    for rx in [&mut rx0, &mut rx1] {
+        assert_eq!(rx.recv().await.unwrap().sn, 1);
+        assert_eq!(rx.recv().await.unwrap().sn, 2);
+        assert!(rx.try_recv().is_err());
+    }
+}
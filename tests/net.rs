@@ -0,0 +1,575 @@
+mod common;
+
+use axum::extract::ws::{Message, WebSocketUpgrade};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::Router;
+use common::{bot, event, login, Recorder, StubSdk};
+use futures_util::{SinkExt, StreamExt};
+use hyper::StatusCode;
+use satori::*;
+use serde_json::{json, Value};
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+fn free_port() -> u16 {
+    TcpListener::bind((LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+async fn events(ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(|mut socket| async move {
+        // IDENTIFY
+        socket.recv().await;
+        let frames = [
+            json!({"op": 4, "body": {"logins": [
+                {"platform": "p", "self_id": "B", "status": 1},
+                {"self_id": "C", "status": 1},
+                {"platform": "p", "status": 1},
+            ]}})
+            .to_string(),
+            json!({"op": 42, "body": null}).to_string(),
+            json!({"op": 300, "body": null}).to_string(),
+            json!({"op": 0, "body": {"sn": "not a number"}}).to_string(),
+            "not json".to_owned(),
+            json!({"op": 0, "body": {
+                "sn": 7, "type": "message-created", "platform": "p", "self_id": "B", "timestamp": 0
+            }})
+            .to_string(),
+        ];
+        for frame in frames {
+            if socket.send(Message::Text(frame)).await.is_err() {
+                return;
+            }
+        }
+        while let Some(Ok(_)) = socket.recv().await {}
+    })
+}
+
+fn stub_server() -> u16 {
+    let listener = TcpListener::bind((LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let router = Router::new()
+        .route("/v1/events", get(events))
+        .route("/v1/ok", post(|| async { r#"{"ok":true}"# }))
+        .route(
+            "/v1/meta",
+            post(|| async {
+                axum::Json(json!({"logins": [{"platform": "p", "self_id": "B", "status": 1}]}))
+            }),
+        )
+        .route(
+            "/v1/teapot",
+            post(|| async { (StatusCode::IM_A_TEAPOT, "short and stout") }),
+        )
+        .route(
+            "/v1/broken",
+            post(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "boom") }),
+        )
+        .route(
+            "/v1/slow",
+            post(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                "{}"
+            }),
+        )
+        .route("/v1/binary", post(|| async { vec![0xffu8, 0xfe, 0x00] }));
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service()),
+    );
+    port
+}
+
+async fn connected_sdk() -> (
+    Arc<Satori<NetSDK, Recorder>>,
+    mpsc::UnboundedReceiver<Event>,
+) {
+    let port = stub_server();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let s = Satori::new(NetSDK::default(), Recorder(tx)).await;
+    let config = NetSDKConfig {
+        host: LOCALHOST,
+        port,
+        authorize: None,
+        reconnect: Default::default(),
+        api_timeout: Duration::from_millis(300),
+        mode: Default::default(),
+    };
+    s.start(vec![config], ()).await;
+    // the only well formed event comes after every malformed frame
+    let event = timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.sn, 7);
+    (s, rx)
+}
+
+#[tokio::test]
+async fn sdk_survives_malformed_frames() {
+    let (s, mut rx) = connected_sdk().await;
+    assert!(rx.try_recv().is_err());
+    // logins without platform or self_id are skipped
+    for id in ["B", "C"] {
+        let bot = BotId {
+            platform: "p".to_owned(),
+            id: id.to_owned(),
+        };
+        let r = s.call_api::<Value>("ok", &bot, json!({})).await;
+        assert_eq!(r.is_ok(), id == "B", "{r:?}");
+    }
+    s.shutdown().await;
+}
+
+#[tokio::test]
+async fn sdk_maps_http_failures() {
+    let (s, _rx) = connected_sdk().await;
+    let ok: Value = s.call_api("ok", &bot(), json!({})).await.unwrap();
+    assert_eq!(ok, json!({"ok": true}));
+    match s.call_api::<Value>("teapot", &bot(), json!({})).await {
+        Err(CallApiError::UnexpectedStatus(418, body)) => assert_eq!(body, "short and stout"),
+        r => panic!("unexpected {r:?}"),
+    }
+    match s.call_api::<Value>("broken", &bot(), json!({})).await {
+        Err(CallApiError::ServerError(500, body)) => assert_eq!(body, "boom"),
+        r => panic!("unexpected {r:?}"),
+    }
+    match s.call_api::<Value>("missing", &bot(), json!({})).await {
+        Err(e @ CallApiError::NotFound(_)) => assert_eq!(e.status(), 404),
+        r => panic!("unexpected {r:?}"),
+    }
+    match s.call_api::<Value>("slow", &bot(), json!({})).await {
+        Err(e @ CallApiError::Timeout) => assert_eq!(e.status(), 504),
+        r => panic!("unexpected {r:?}"),
+    }
+    match s.call_api::<Value>("binary", &bot(), json!({})).await {
+        Err(e @ CallApiError::Protocol(_)) => assert_eq!(e.status(), 502),
+        r => panic!("unexpected {r:?}"),
+    }
+    s.shutdown().await;
+}
+
+#[tokio::test]
+async fn sdk_transport_error() {
+    let sdk = NetSDK::default();
+    let net = NetSDKConfig {
+        host: LOCALHOST,
+        port: free_port(),
+        authorize: None,
+        reconnect: Default::default(),
+        api_timeout: Duration::from_secs(1),
+        mode: Default::default(),
+    };
+    sdk.bots.write().await.insert(bot(), (net, login("B")));
+    assert!(matches!(
+        SdkT::call_api(&sdk, "ok", &bot(), json!({})).await,
+        Err(CallApiError::Transport(_))
+    ));
+}
+
+#[tokio::test]
+async fn app_survives_malformed_frames() {
+    let port = free_port();
+    let s = Satori::new_sdk(StubSdk::new());
+    let config = NetAPPConfig {
+        host: LOCALHOST,
+        port,
+        authorize: None,
+        tokens: vec![],
+        webhooks: vec![],
+        upload_limit: 1024 * 1024,
+    };
+    s.start((), vec![config]).await;
+    let url = format!("ws://127.0.0.1:{port}/v1/events");
+    let mut ws = loop {
+        match tokio_tungstenite::connect_async(&url).await {
+            Ok((ws, _)) => break ws,
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    use tokio_tungstenite::tungstenite::Message;
+    for frame in [
+        json!({"op": 42, "body": null}).to_string(),
+        json!({"op": 3, "body": "not an identify"}).to_string(),
+        "not json".to_owned(),
+        json!({"op": 1, "body": null}).to_string(),
+    ] {
+        ws.send(Message::Text(frame)).await.unwrap();
+    }
+    let reply = loop {
+        match timeout(Duration::from_secs(5), ws.next()).await.unwrap() {
+            Some(Ok(Message::Text(text))) => break text,
+            Some(Ok(_)) => continue,
+            r => panic!("connection lost: {r:?}"),
+        }
+    };
+    let reply: Value = serde_json::from_str(&reply).unwrap();
+    // READY for the anonymous identify, the server is open
+    assert_eq!(reply["op"], 4);
+    let reply = loop {
+        match timeout(Duration::from_secs(5), ws.next()).await.unwrap() {
+            Some(Ok(Message::Text(text))) => break text,
+            Some(Ok(_)) => continue,
+            r => panic!("connection lost: {r:?}"),
+        }
+    };
+    let reply: Value = serde_json::from_str(&reply).unwrap();
+    assert_eq!(reply["op"], 2);
+    s.shutdown().await;
+}
+
+#[tokio::test]
+async fn webhook_retries_hung_target() {
+    let listener = TcpListener::bind((LOCALHOST, 0)).unwrap();
+    let target = listener.local_addr().unwrap().port();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let router = Router::new().route(
+        "/hook",
+        post(move |axum::Json(event): axum::Json<Event>| async move {
+            // the first delivery never answers
+            if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                std::future::pending::<()>().await;
+            }
+            tx.send(event.sn).ok();
+        }),
+    );
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service()),
+    );
+    let mut webhook = WebhookConfig::new(format!("http://127.0.0.1:{target}/hook"));
+    webhook.timeout = Duration::from_millis(200);
+    webhook.retry.initial_delay = Duration::from_millis(10);
+    let s = Satori::new_sdk(StubSdk::new());
+    let config = NetAPPConfig {
+        host: LOCALHOST,
+        port: free_port(),
+        authorize: None,
+        tokens: vec![],
+        webhooks: vec![webhook],
+        upload_limit: 1024 * 1024,
+    };
+    s.start((), vec![config]).await;
+    s.handle_event(event(3, "message-created", json!({}))).await;
+    let sn = timeout(Duration::from_secs(5), rx.recv()).await.unwrap();
+    // numbered by the NetApp
+    assert_eq!(sn, Some(1));
+    s.shutdown().await;
+}
+
+#[tokio::test]
+async fn sdk_webhook_mode() {
+    let port = stub_server();
+    let listen = free_port();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let s = Satori::new(NetSDK::default(), Recorder(tx)).await;
+    let config = NetSDKConfig {
+        host: LOCALHOST,
+        port,
+        authorize: Some("secret".to_owned()),
+        reconnect: Default::default(),
+        api_timeout: Duration::from_secs(1),
+        mode: NetSDKMode::Webhook {
+            host: LOCALHOST,
+            port: listen,
+            // a missing leading slash is added
+            path: "hook".to_owned(),
+        },
+    };
+    s.start(vec![config], ()).await;
+    // logins are known from /v1/meta, before any event of the bot
+    let ok = timeout(Duration::from_secs(5), async {
+        loop {
+            match s.call_api::<Value>("ok", &bot(), json!({})).await {
+                Ok(ok) => break ok,
+                Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(ok, json!({"ok": true}));
+    let event = json!({
+        "sn": 9, "type": "message-created", "platform": "p", "self_id": "B", "timestamp": 0
+    });
+    for (token, status) in [
+        ("secre", StatusCode::UNAUTHORIZED),
+        ("secret!", StatusCode::UNAUTHORIZED),
+        ("secret", StatusCode::OK),
+    ] {
+        let req = hyper::Request::post(format!("http://127.0.0.1:{listen}/hook"))
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {token}"))
+            .body(hyper::Body::from(event.to_string()))
+            .unwrap();
+        let resp = hyper::Client::new().request(req).await.unwrap();
+        assert_eq!(resp.status(), status);
+    }
+    let event = timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.sn, 9);
+    s.shutdown().await;
+}
+
+#[tokio::test]
+async fn upload_round_trip() {
+    let port = free_port();
+    let sdk = StubSdk::new();
+    let received = sdk.uploads.clone();
+    let app = Satori::new_sdk(sdk);
+    let config = NetAPPConfig {
+        host: LOCALHOST,
+        port,
+        authorize: None,
+        tokens: vec![],
+        webhooks: vec![],
+        upload_limit: 4 * 1024 * 1024,
+    };
+    app.start((), vec![config]).await;
+    let sdk = NetSDK::default();
+    let net = NetSDKConfig {
+        host: LOCALHOST,
+        port,
+        authorize: None,
+        reconnect: Default::default(),
+        api_timeout: Duration::from_secs(5),
+        mode: Default::default(),
+    };
+    sdk.bots.write().await.insert(bot(), (net, login("B")));
+    // above the 2 MB axum default
+    let image: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let files = vec![
+        UploadFile {
+            name: "image".to_owned(),
+            filename: Some("a \"b\".png".to_owned()),
+            content_type: Some("image/png".to_owned()),
+            data: image.clone(),
+        },
+        UploadFile {
+            name: "note".to_owned(),
+            filename: None,
+            content_type: None,
+            data: b"\r\n--not a boundary\r\n".to_vec(),
+        },
+    ];
+    let urls = loop {
+        match SdkT::upload(&sdk, &bot(), files.clone()).await {
+            Err(CallApiError::Transport(_)) => tokio::time::sleep(Duration::from_millis(20)).await,
+            r => break r.unwrap(),
+        }
+    };
+    assert_eq!(urls["image"], "internal:image");
+    assert_eq!(urls["note"], "internal:note");
+    let received = std::mem::take(&mut *received.lock().unwrap());
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0].data, image);
+    assert_eq!(received[0].filename.as_deref(), Some("a %22b%22.png"));
+    assert_eq!(received[0].content_type.as_deref(), Some("image/png"));
+    assert_eq!(received[1].data, files[1].data);
+    assert_eq!(
+        received[1].content_type.as_deref(),
+        Some("application/octet-stream")
+    );
+    let huge = vec![UploadFile {
+        name: "huge".to_owned(),
+        filename: None,
+        content_type: None,
+        data: vec![0; 5 * 1024 * 1024],
+    }];
+    let r = SdkT::upload(&sdk, &bot(), huge).await;
+    assert!(
+        matches!(r, Err(CallApiError::UnexpectedStatus(413, _))),
+        "{r:?}"
+    );
+    app.shutdown().await;
+}
+
+type Ws =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+fn app_config(port: u16) -> NetAPPConfig {
+    NetAPPConfig {
+        host: LOCALHOST,
+        port,
+        authorize: None,
+        tokens: vec![],
+        webhooks: vec![],
+        upload_limit: 1024 * 1024,
+    }
+}
+
+/// Connects to the NetApp on `port` and identifies, READY is left unread.
+async fn identify(port: u16, token: &str, sequence: Option<i64>) -> Ws {
+    let url = format!("ws://127.0.0.1:{port}/v1/events");
+    let mut ws = loop {
+        match tokio_tungstenite::connect_async(&url).await {
+            Ok((ws, _)) => break ws,
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    let identify = json!({"op": 3, "body": {"token": token, "sequence": sequence}});
+    let frame = tokio_tungstenite::tungstenite::Message::Text(identify.to_string());
+    ws.send(frame).await.unwrap();
+    ws
+}
+
+/// The next signal, or the close code when the server closes.
+async fn signal(ws: &mut Ws) -> Result<Value, u16> {
+    use tokio_tungstenite::tungstenite::Message;
+    loop {
+        match timeout(Duration::from_secs(5), ws.next()).await.unwrap() {
+            Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text).unwrap()),
+            Some(Ok(Message::Close(frame))) => return Err(frame.map_or(0, |f| f.code.into())),
+            Some(Ok(_)) => continue,
+            r => panic!("connection lost: {r:?}"),
+        }
+    }
+}
+
+async fn event_sn(ws: &mut Ws) -> i64 {
+    let signal = signal(ws).await.unwrap();
+    assert_eq!(signal["op"], 0, "{signal}");
+    signal["body"]["sn"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn app_numbers_events_and_replays() {
+    let port = free_port();
+    let s = Satori::new_sdk(StubSdk::new());
+    s.start((), vec![app_config(port)]).await;
+    // the numbers of the sdk are not used
+    s.handle_event(event(100, "x", json!({}))).await;
+    s.handle_event(event(5, "x", json!({}))).await;
+    // a client resuming from before a restart of the server is ahead
+    let mut ahead = identify(port, "", Some(1000)).await;
+    assert_eq!(signal(&mut ahead).await.unwrap()["op"], 4);
+    let mut resumed = identify(port, "", Some(1)).await;
+    assert_eq!(signal(&mut resumed).await.unwrap()["op"], 4);
+    assert_eq!(event_sn(&mut resumed).await, 2);
+    s.handle_event(event(7, "x", json!({}))).await;
+    assert_eq!(event_sn(&mut ahead).await, 3);
+    assert_eq!(event_sn(&mut resumed).await, 3);
+    s.shutdown().await;
+}
+
+/// Posts `data` to `path` of the NetApp on `port` on behalf of bot `id`.
+async fn request(port: u16, path: &str, token: Option<&str>, id: &str, data: Value) -> StatusCode {
+    let mut req = hyper::Request::post(format!("http://127.0.0.1:{port}{path}"))
+        .header("Content-Type", "application/json")
+        .header("X-Platform", "p")
+        .header("X-Self-ID", id);
+    if let Some(token) = token {
+        req = req.header("Authorization", format!("Bearer {token}"));
+    }
+    let req = req.body(hyper::Body::from(data.to_string())).unwrap();
+    hyper::Client::new().request(req).await.unwrap().status()
+}
+
+fn ready_logins(ready: Value) -> Vec<String> {
+    assert_eq!(ready["op"], 4, "{ready}");
+    let logins = ready["body"]["logins"].as_array().unwrap();
+    logins
+        .iter()
+        .map(|l| l["self_id"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn app_checks_tokens_and_scopes() {
+    let port = free_port();
+    let s = Satori::new_sdk(StubSdk::new().logins(&["B", "C"]));
+    let config = NetAPPConfig {
+        authorize: Some("admin".to_owned()),
+        tokens: vec![ScopedToken {
+            token: "scoped".to_owned(),
+            bots: vec![bot()],
+        }],
+        ..app_config(port)
+    };
+    s.start((), vec![config]).await;
+    let mut ws = identify(port, "nope", None).await;
+    assert_eq!(signal(&mut ws).await, Err(3000));
+    let mut ws = identify(port, "scoped", None).await;
+    assert_eq!(ready_logins(signal(&mut ws).await.unwrap()), ["B"]);
+    let mut ws = identify(port, "admin", None).await;
+    assert_eq!(ready_logins(signal(&mut ws).await.unwrap()), ["B", "C"]);
+
+    let api = |token, id| request(port, "/v1/message.get", token, id, json!({}));
+    assert_eq!(api(None, "B").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(api(Some("nope"), "B").await, StatusCode::UNAUTHORIZED);
+    // a prefix of a valid token is not enough
+    assert_eq!(api(Some("admi"), "B").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(api(Some("admin"), "C").await, StatusCode::OK);
+    assert_eq!(api(Some("scoped"), "B").await, StatusCode::OK);
+    assert_eq!(api(Some("scoped"), "C").await, StatusCode::FORBIDDEN);
+    let internal = request(port, "/v1/internal/x", Some("scoped"), "C", json!({}));
+    assert_eq!(internal.await, StatusCode::FORBIDDEN);
+
+    let hook = json!({"url": "http://127.0.0.1:1/hook"});
+    for action in ["webhook.create", "webhook.delete"] {
+        let path = format!("/v1/meta/{action}");
+        let r = request(port, &path, Some("scoped"), "B", hook.clone());
+        assert_eq!(r.await, StatusCode::FORBIDDEN);
+        let r = request(port, &path, None, "B", hook.clone());
+        assert_eq!(r.await, StatusCode::UNAUTHORIZED);
+    }
+    // past the checks, nothing to delete
+    let r = request(port, "/v1/meta/webhook.delete", Some("admin"), "B", hook);
+    assert_eq!(r.await, StatusCode::NOT_FOUND);
+    s.shutdown().await;
+}
+
+#[tokio::test]
+async fn app_rejects_unsupported_webhooks() {
+    let port = free_port();
+    let s = Satori::new_sdk(StubSdk::new());
+    let valid = "http://127.0.0.1:1/hook";
+    let config = NetAPPConfig {
+        webhooks: vec![
+            WebhookConfig::new("https://127.0.0.1:1/hook"),
+            WebhookConfig::new(valid),
+        ],
+        ..app_config(port)
+    };
+    s.start((), vec![config]).await;
+    // wait for the server
+    identify(port, "", None).await;
+    let create = |url: &str| {
+        let hook = json!({ "url": url });
+        request(port, "/v1/meta/webhook.create", None, "B", hook)
+    };
+    for url in [
+        "https://x/hook",
+        "ftp://x/hook",
+        "/hook",
+        "x/hook",
+        "not a url",
+    ] {
+        assert_eq!(create(url).await, StatusCode::BAD_REQUEST, "{url}");
+    }
+    assert_eq!(create("http://127.0.0.1:2/hook").await, StatusCode::OK);
+    let req = hyper::Request::post(format!("http://127.0.0.1:{port}/v1/meta"))
+        .body(hyper::Body::empty())
+        .unwrap();
+    let resp = hyper::Client::new().request(req).await.unwrap();
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    let meta: Value = serde_json::from_slice(&body).unwrap();
+    let mut webhooks: Vec<_> = meta["webhooks"].as_array().unwrap().clone();
+    webhooks.sort_by_key(|w| w.to_string());
+    assert_eq!(webhooks, [json!(valid), json!("http://127.0.0.1:2/hook")]);
+    s.shutdown().await;
+}
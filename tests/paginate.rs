@@ -0,0 +1,143 @@
+mod common;
+
+use common::{bot, StubSdk};
+use futures_util::StreamExt;
+use satori::*;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+type Tokens = Arc<Mutex<Vec<Option<String>>>>;
+type Page = std::future::Ready<Result<List<i32>, CallApiError>>;
+
+/// A fetch serving `pages` as `(token, data, next)` and recording the tokens
+/// it is called with. Unknown tokens fail.
+fn fake(
+    pages: &[(Option<&str>, &[i32], Option<&str>)],
+) -> (Tokens, impl FnMut(Option<String>) -> Page) {
+    let tokens = Tokens::default();
+    let pages: Vec<_> = pages
+        .iter()
+        .map(|(token, data, next)| {
+            let list = List {
+                data: data.to_vec(),
+                next: next.map(str::to_owned),
+            };
+            (token.map(str::to_owned), list)
+        })
+        .collect();
+    let fetch = {
+        let tokens = tokens.clone();
+        move |token: Option<String>| {
+            tokens.lock().unwrap().push(token.clone());
+            let page = pages.iter().find(|(t, _)| *t == token);
+            std::future::ready(match page {
+                Some((_, list)) => Ok(list.clone()),
+                None => Err(CallApiError::Timeout),
+            })
+        }
+    };
+    (tokens, fetch)
+}
+
+fn assert_tokens(tokens: &Tokens, expected: &[Option<&str>]) {
+    let tokens = tokens.lock().unwrap();
+    let tokens: Vec<_> = tokens.iter().map(Option::as_deref).collect();
+    assert_eq!(tokens, expected);
+}
+
+#[tokio::test]
+async fn fetches_pages_lazily() {
+    let (called, fetch) = fake(&[
+        (None, &[1, 2], Some("a")),
+        (Some("a"), &[], Some("b")),
+        (Some("b"), &[3], None),
+    ]);
+    let mut items = Box::pin(paginate(fetch));
+    assert_eq!(items.next().await.unwrap().unwrap(), 1);
+    assert_eq!(items.next().await.unwrap().unwrap(), 2);
+    assert_tokens(&called, &[None]);
+    // an empty page is skipped over
+    assert_eq!(items.next().await.unwrap().unwrap(), 3);
+    assert_tokens(&called, &[None, Some("a"), Some("b")]);
+    // nothing is fetched after the last page
+    assert!(items.next().await.is_none());
+    assert_tokens(&called, &[None, Some("a"), Some("b")]);
+}
+
+#[tokio::test]
+async fn stops_on_the_first_error() {
+    let (called, fetch) = fake(&[(None, &[1], Some("missing"))]);
+    let items: Vec<_> = paginate(fetch).collect().await;
+    assert!(matches!(items[..], [Ok(1), Err(CallApiError::Timeout)]));
+    assert_tokens(&called, &[None, Some("missing")]);
+}
+
+#[tokio::test]
+async fn stops_on_a_page_pointing_at_itself() {
+    let (called, fetch) = fake(&[(None, &[1], Some("a")), (Some("a"), &[2], Some("a"))]);
+    let items: Vec<_> = paginate(fetch).map(Result::unwrap).collect().await;
+    assert_eq!(items, [1, 2]);
+    assert_tokens(&called, &[None, Some("a")]);
+}
+
+fn bidi(token: Option<String>) -> std::future::Ready<Result<BidiList<String>, CallApiError>> {
+    // pages are numbered, prev goes down and next goes up
+    let page: i32 = token.map_or(0, |t| t.parse().unwrap());
+    std::future::ready(Ok(BidiList {
+        data: vec![page.to_string()],
+        prev: (page > -2).then(|| (page - 1).to_string()),
+        next: (page < 2).then(|| (page + 1).to_string()),
+    }))
+}
+
+#[tokio::test]
+async fn bidi_follows_the_direction() {
+    let walk = |direction| async move {
+        let items = paginate_bidi(direction, bidi);
+        items.map(Result::unwrap).collect::<Vec<_>>().await
+    };
+    assert_eq!(walk(Direction::Before).await, ["0", "-1", "-2"]);
+    assert_eq!(walk(Direction::After).await, ["0", "1", "2"]);
+    assert_eq!(walk(Direction::Around).await, ["0"]);
+}
+
+#[tokio::test]
+async fn messages_start_at_the_given_token() {
+    let sdk = StubSdk::new().answer(|_, data| {
+        let page = match data["next"].as_str() {
+            Some("start") => {
+                json!({"data": [{"id": "1", "content": ""}], "prev": "p1", "next": "n1"})
+            }
+            Some("p1") => json!({"data": [{"id": "2", "content": ""}]}),
+            next => panic!("unexpected token {next:?}"),
+        };
+        Ok(page.to_string())
+    });
+    let calls = sdk.calls.clone();
+    let s = Satori::new_sdk(sdk);
+    let id = bot();
+    let request = MessageListRequest {
+        channel_id: "c".to_owned(),
+        next: Some("start".to_owned()),
+        direction: None,
+        limit: Some(1),
+        order: None,
+    };
+    let messages: Vec<_> = s.bot(&id).messages(request).collect().await;
+    let ids: Vec<_> = messages.into_iter().map(|m| m.unwrap().id).collect();
+    assert_eq!(ids, ["1", "2"]);
+    let calls = calls.lock().unwrap();
+    assert_eq!(
+        *calls,
+        [
+            (
+                "message.list".to_owned(),
+                json!({"channel_id": "c", "next": "start", "limit": 1})
+            ),
+            (
+                "message.list".to_owned(),
+                json!({"channel_id": "c", "next": "p1", "limit": 1})
+            ),
+        ]
+    );
+}
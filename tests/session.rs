@@ -0,0 +1,111 @@
+mod common;
+
+use common::{event, message, Recorder, StubSdk};
+use satori::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+async fn satori() -> (Arc<Satori<StubSdk, Recorder>>, common::Calls) {
+    let sdk = StubSdk::new().answer(|api, _| match api {
+        "message.create" => Ok("[]".to_owned()),
+        _ => Ok("null".to_owned()),
+    });
+    let calls = sdk.calls.clone();
+    let (tx, _rx) = mpsc::unbounded_channel();
+    (Satori::new(sdk, Recorder(tx)).await, calls)
+}
+
+#[tokio::test]
+async fn actions_target_the_event() {
+    let (s, calls) = satori().await;
+    let session = Session::new(&s, message(7, "c", "hi"));
+    session.reply("a").await.unwrap();
+    session.quote_reply("b").await.unwrap();
+    session.react("+1").await.unwrap();
+    session.delete().await.unwrap();
+    session.send("d", "e").await.unwrap();
+    let calls = std::mem::take(&mut *calls.lock().unwrap());
+    let expected: Vec<(String, Value)> = vec![
+        (
+            "message.create".to_owned(),
+            json!({"channel_id": "c", "content": "a"}),
+        ),
+        (
+            "message.create".to_owned(),
+            json!({"channel_id": "c", "content": "<quote id=\"7\"/>b"}),
+        ),
+        (
+            "reaction.create".to_owned(),
+            json!({"channel_id": "c", "message_id": "7", "emoji": "+1"}),
+        ),
+        (
+            "message.delete".to_owned(),
+            json!({"channel_id": "c", "message_id": "7"}),
+        ),
+        (
+            "message.create".to_owned(),
+            json!({"channel_id": "d", "content": "e"}),
+        ),
+    ];
+    assert_eq!(calls, expected);
+}
+
+#[tokio::test]
+async fn actions_need_a_channel_and_message() {
+    let (s, calls) = satori().await;
+    let no_channel = Session::new(
+        &s,
+        event(1, "x", json!({"message": {"id": "m", "content": ""}})),
+    );
+    let no_message = Session::new(
+        &s,
+        event(1, "x", json!({"channel": {"id": "c", "type": 0}})),
+    );
+    let bad_request = |r: Result<_, CallApiError>, missing: &str| match r {
+        Err(CallApiError::BadRequest(e)) => assert_eq!(e, format!("event has no {missing}")),
+        r => panic!("unexpected {r:?}"),
+    };
+    bad_request(no_channel.reply("a").await.map(drop), "channel");
+    bad_request(no_channel.quote_reply("a").await.map(drop), "channel");
+    bad_request(no_channel.react("+1").await, "channel");
+    bad_request(no_channel.delete().await, "channel");
+    bad_request(no_message.quote_reply("a").await.map(drop), "message");
+    bad_request(no_message.react("+1").await, "message");
+    bad_request(no_message.delete().await, "message");
+    assert!(calls.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn prompts_survive_panicking_matchers() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let s = Satori::new(StubSdk::new(), Recorder(tx)).await;
+    let session = Session::new(&s, message(1, "c", "ask"));
+    let broken = tokio::spawn({
+        let session = session.clone();
+        async move {
+            let matcher = |_: &Event| -> bool { panic!("matcher failed") };
+            session
+                .prompt_with(Duration::from_millis(200), matcher)
+                .await
+        }
+    });
+    let asked = tokio::spawn({
+        let session = session.clone();
+        async move { session.prompt(Duration::from_secs(5)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    s.handle_event(message(2, "c", "yes")).await;
+    let answer = asked.await.unwrap().unwrap();
+    assert_eq!(answer.event.sn, 2);
+    assert!(broken.await.unwrap().is_none());
+    // prompts still work and other events reach the app
+    let asked = tokio::spawn(async move { session.prompt(Duration::from_secs(5)).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    s.handle_event(message(3, "c", "again")).await;
+    assert_eq!(asked.await.unwrap().unwrap().event.sn, 3);
+    s.handle_event(message(4, "d", "hi")).await;
+    assert_eq!(rx.recv().await.unwrap().sn, 4);
+    assert!(rx.try_recv().is_err());
+}
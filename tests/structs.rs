@@ -0,0 +1,193 @@
+use satori::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+// every field of the fixture comes back with the same value, absent fields
+// may come back as null
+fn assert_contains(out: &Value, fixture: &Value, path: &str) {
+    match (out, fixture) {
+        (Value::Object(out), Value::Object(fixture)) => {
+            for (k, v) in fixture {
+                let got = out.get(k).unwrap_or(&Value::Null);
+                assert_contains(got, v, &format!("{path}.{k}"));
+            }
+        }
+        (Value::Array(out), Value::Array(fixture)) => {
+            assert_eq!(out.len(), fixture.len(), "{path}");
+            for (i, (o, f)) in out.iter().zip(fixture).enumerate() {
+                assert_contains(o, f, &format!("{path}[{i}]"));
+            }
+        }
+        _ => assert_eq!(out, fixture, "{path}"),
+    }
+}
+
+fn round_trip<T: Serialize + DeserializeOwned>(fixture: Value) -> T {
+    let parsed: T = serde_json::from_value(fixture.clone()).unwrap();
+    let out = serde_json::to_value(&parsed).unwrap();
+    assert_contains(&out, &fixture, "$");
+    let again: T = serde_json::from_value(out.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&again).unwrap(), out);
+    parsed
+}
+
+fn user() -> Value {
+    json!({
+        "id": "1001",
+        "name": "alice",
+        "nick": "Al",
+        "avatar": "https://example.com/alice.png",
+        "is_bot": false
+    })
+}
+
+fn channel() -> Value {
+    json!({"id": "2001", "type": 0, "name": "general", "parent_id": "2000"})
+}
+
+fn guild() -> Value {
+    json!({"id": "3001", "name": "Satori", "avatar": "https://example.com/guild.png"})
+}
+
+fn member() -> Value {
+    json!({
+        "user": user(),
+        "nick": "Alice in Satori",
+        "avatar": "https://example.com/member.png",
+        "joined_at": 1700000000000i64
+    })
+}
+
+fn login() -> Value {
+    json!({
+        "sn": 1,
+        "platform": "discord",
+        "user": {"id": "9001", "name": "bot", "is_bot": true},
+        "status": 1,
+        "adapter": "discord",
+        "features": ["message.delete", "guild.plain"],
+        "proxy_urls": ["https://cdn.discordapp.com/"]
+    })
+}
+
+fn message() -> Value {
+    json!({
+        "id": "4001",
+        "content": "hello <at id=\"1001\"/>",
+        "channel": channel(),
+        "guild": guild(),
+        "member": member(),
+        "user": user(),
+        "quote": {"id": "4000", "content": "quoted"},
+        "created_at": 1700000000000i64,
+        "updated_at": 1700000001000i64
+    })
+}
+
+#[test]
+fn resources() {
+    let user: User = round_trip(user());
+    assert_eq!(user.nick.as_deref(), Some("Al"));
+    let channel: Channel = round_trip(channel());
+    assert!(matches!(channel.ty, ChannelType::Text));
+    let direct: Channel = round_trip(json!({"id": "d", "type": 1}));
+    assert!(matches!(direct.ty, ChannelType::Direct));
+    let category: Channel = round_trip(json!({"id": "g", "type": 2}));
+    assert!(matches!(category.ty, ChannelType::Category));
+    let voice: Channel = round_trip(json!({"id": "v", "type": 3}));
+    assert!(matches!(voice.ty, ChannelType::Voice));
+    round_trip::<Guild>(guild());
+    let member: GuildMember = round_trip(member());
+    assert_eq!(member.nick.as_deref(), Some("Alice in Satori"));
+    round_trip::<GuildRole>(json!({"id": "5001", "name": "admin"}));
+    let login: Login = round_trip(login());
+    assert_eq!(login.sn, Some(1));
+    assert_eq!(login.features.len(), 2);
+    let message: Message = round_trip(message());
+    assert_eq!(message.quote.unwrap().id, "4000");
+    let minimal: Message = round_trip(json!({"id": "4002"}));
+    assert_eq!(minimal.content, "");
+}
+
+#[test]
+fn lists() {
+    let list: List<Channel> = round_trip(json!({"data": [channel()], "next": "token"}));
+    assert_eq!(list.next.as_deref(), Some("token"));
+    let list: BidiList<Message> =
+        round_trip(json!({"data": [message(), message()], "prev": "p", "next": null}));
+    assert_eq!(list.data.len(), 2);
+    assert_eq!(list.prev.as_deref(), Some("p"));
+}
+
+fn event(fields: Value) -> Value {
+    let mut event = json!({
+        "type": "message-created",
+        "platform": "discord",
+        "self_id": "9001",
+        "timestamp": 1700000000000i64
+    });
+    event
+        .as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    event
+}
+
+#[test]
+fn events() {
+    let e: Event = round_trip(event(json!({
+        "sn": 42,
+        "channel": channel(),
+        "guild": guild(),
+        "member": member(),
+        "user": user(),
+        "message": message(),
+        "login": login()
+    })));
+    assert_eq!(e.sn, 42);
+    assert!(e.extra.is_empty());
+    let e: Event = round_trip(event(json!({
+        "sn": 43,
+        "type": "interaction/command",
+        "argv": {"name": "echo", "arguments": ["hi", 1], "options": {"loud": true}}
+    })));
+    let argv = e.argv.unwrap();
+    assert_eq!(argv.name, "echo");
+    assert_eq!(argv.options["loud"], json!(true));
+    let e: Event = round_trip(event(json!({
+        "sn": 44,
+        "type": "interaction/button",
+        "button": {"id": "btn"}
+    })));
+    assert_eq!(e.button.unwrap().id, "btn");
+    let e: Event = round_trip(event(json!({
+        "sn": 45,
+        "type": "internal",
+        "_type": "raw",
+        "_data": {"a": 1}
+    })));
+    assert_eq!(e.extra["_data"], json!({"a": 1}));
+    assert!(!e.extra.contains_key("sn") && !e.extra.contains_key("id"));
+}
+
+#[test]
+fn renamed_fields() {
+    // older servers
+    let e: Event = serde_json::from_value(event(json!({"id": 7}))).unwrap();
+    assert_eq!(e.sn, 7);
+    // servers in transition send both, the new name wins
+    let e: Event = serde_json::from_value(event(json!({"id": 7, "sn": 8}))).unwrap();
+    assert_eq!(e.sn, 8);
+    assert!(e.extra.is_empty());
+    let out = serde_json::to_value(&e).unwrap();
+    assert_eq!((&out["sn"], &out["id"]), (&json!(8), &json!(8)));
+    assert!(serde_json::from_value::<Event>(event(json!({}))).is_err());
+
+    let m: GuildMember = serde_json::from_value(json!({"name": "a"})).unwrap();
+    assert_eq!(m.nick.as_deref(), Some("a"));
+    let m: GuildMember = serde_json::from_value(json!({"name": "a", "nick": "b"})).unwrap();
+    assert_eq!(m.nick.as_deref(), Some("b"));
+    let out = serde_json::to_value(&m).unwrap();
+    assert_eq!((&out["nick"], &out["name"]), (&json!("b"), &json!("b")));
+}
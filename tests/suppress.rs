@@ -0,0 +1,74 @@
+mod common;
+
+use async_trait::async_trait;
+use common::{message, StubSdk};
+use satori::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Prompts on `ask` and reports what answered it, reports anything else.
+struct Asker(mpsc::UnboundedSender<String>);
+
+#[async_trait]
+impl AppT for Asker {
+    type Config = ();
+    async fn start<S, A>(&self, _s: &Arc<Satori<S, A>>, _config: ()) -> Vec<JoinHandle<()>>
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        vec![]
+    }
+    async fn handle_event<S, A>(&self, s: &Arc<Satori<S, A>>, event: Event)
+    where
+        S: SdkT + Send + Sync + 'static,
+        A: AppT + Send + Sync + 'static,
+    {
+        let session = Session::new(s, event);
+        let content = session.message().unwrap().content.clone();
+        if content != "ask" {
+            self.0.send(format!("event {content}")).ok();
+            return;
+        }
+        // anything in the channel answers
+        let answer = session
+            .prompt_with(Duration::from_secs(5), |e| e.ty == "message-created")
+            .await;
+        let answer = answer.map(|a| a.message().unwrap().content.clone());
+        self.0.send(format!("answer {answer:?}")).ok();
+    }
+}
+
+fn own_message(sn: i64, content: &str) -> Event {
+    let mut event = message(sn, "c", content);
+    event.user.as_mut().unwrap().id = "B".to_owned();
+    event
+}
+
+#[tokio::test]
+async fn suppressed_events_do_not_answer_prompts() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let suppress = Suppress::new()
+        .self_messages()
+        .duplicates(Duration::from_secs(60));
+    let counters = suppress.counters();
+    let s = Satori::new(StubSdk::new(), Asker(tx).layer(suppress)).await;
+    let asking = {
+        let s = s.clone();
+        tokio::spawn(async move { s.handle_event(message(1, "c", "ask")).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // the bot echoing itself and a replay of the question
+    s.handle_event(own_message(2, "self")).await;
+    s.handle_event(message(1, "c", "ask")).await;
+    assert_eq!(counters.self_messages(), 1);
+    assert_eq!(counters.duplicates(), 1);
+    s.handle_event(message(3, "c", "reply")).await;
+    asking.await.unwrap();
+    assert_eq!(rx.recv().await.unwrap(), "answer Some(\"reply\")");
+    s.handle_event(message(4, "c", "later")).await;
+    assert_eq!(rx.recv().await.unwrap(), "event later");
+    assert!(rx.try_recv().is_err());
+}